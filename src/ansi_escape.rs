@@ -1,3 +1,5 @@
+use std::ffi::OsString;
+
 /// Wraps each line in an ANSI escape sequence while preserving prior ANSI escape sequences.
 ///
 /// ## Why does this exist?
@@ -58,6 +60,57 @@ impl ANSI {
     }
 }
 
+/// Controls whether [`crate::Print`] emits ANSI escape codes.
+///
+/// When colors are disabled, escape codes are removed from all text that [`crate::Print`]
+/// writes on your behalf, including values decorated with the [`crate::style`] helpers.
+/// Output streamed from other processes is left untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Always emit ANSI escape codes.
+    Always,
+    /// Never emit ANSI escape codes.
+    Never,
+    /// Decide based on the environment.
+    ///
+    /// Colors are disabled when `NO_COLOR` is set to a non-empty value. Otherwise, colors are
+    /// enabled when `CLICOLOR_FORCE` is set to a value other than `0`. Otherwise, colors are
+    /// disabled when `TERM=dumb` and enabled in all other cases.
+    ///
+    /// Colors are not disabled when the output is not a TTY, as output is frequently piped to a
+    /// user's terminal, for example via a `git push`.
+    #[default]
+    Auto,
+}
+
+impl ColorChoice {
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => auto_enabled(
+                std::env::var_os("NO_COLOR"),
+                std::env::var_os("CLICOLOR_FORCE"),
+                std::env::var_os("TERM"),
+            ),
+        }
+    }
+}
+
+fn auto_enabled(
+    no_color: Option<OsString>,
+    clicolor_force: Option<OsString>,
+    term: Option<OsString>,
+) -> bool {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        false
+    } else if clicolor_force.is_some_and(|value| !value.is_empty() && value != "0") {
+        true
+    } else {
+        term.as_deref() != Some(std::ffi::OsStr::new("dumb"))
+    }
+}
+
 /// Removes ANSI escape sequences generated from this library from a string.
 ///
 /// Not guaranteed to remove all ANSI escape sequences, only those generated by this library.
@@ -84,10 +137,161 @@ pub fn strip_ansi(contents: impl AsRef<str>) -> String {
     result
 }
 
+/// Neutralizes untrusted escape sequences in a single line of streamed output.
+///
+/// SGR sequences (colors and text styles, i.e. `ESC [ ... m`) are preserved, all other escape
+/// sequences (cursor movement, screen clearing, OSC window titles and hyperlinks, etc.) and
+/// control characters other than tab, newline and carriage return are removed. If the line
+/// contains an SGR sequence, a reset is added at the end of the line (before any trailing
+/// newline) so that styles do not leak onto the next line.
+///
+/// The single byte CSI (`0x9B`) is handled like `ESC [` when it's not part of a UTF-8 character.
+/// Kept SGR sequences are always written with `ESC [`, so valid UTF-8 stays valid.
+pub(crate) fn sanitize_line(line: &[u8]) -> Vec<u8> {
+    let (body, newline) = match line.strip_suffix(b"\n") {
+        Some(body) => (body, true),
+        None => (line, false),
+    };
+
+    let mut result = Vec::with_capacity(line.len());
+    let mut has_sgr = false;
+    let mut index = 0;
+    while index < body.len() {
+        let byte = body[index];
+        index += 1;
+        match byte {
+            // CSI: ESC [ (or 0x9B) parameters intermediates final
+            0x9B => index = skip_csi(body, index, &mut result, &mut has_sgr),
+            0x1B if body.get(index) == Some(&b'[') => {
+                index = skip_csi(body, index + 1, &mut result, &mut has_sgr);
+            }
+            0x1B => match body.get(index) {
+                // OSC, DCS, SOS, PM, APC: terminated by BEL or ST (ESC \)
+                Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+                    index += 1;
+                    while index < body.len() {
+                        if body[index] == 0x07 {
+                            index += 1;
+                            break;
+                        } else if body[index] == 0x1B && body.get(index + 1) == Some(&b'\\') {
+                            index += 2;
+                            break;
+                        }
+                        index += 1;
+                    }
+                }
+                // Other escape sequences: optional intermediates followed by a final byte. A
+                // character that is not ASCII is not a final byte, only the ESC is removed.
+                Some(_) => {
+                    while body.get(index).is_some_and(|b| (0x20..=0x2F).contains(b)) {
+                        index += 1;
+                    }
+                    if body.get(index).is_some_and(|b| (0x30..=0x7E).contains(b)) {
+                        index += 1;
+                    }
+                }
+                None => {}
+            },
+            b'\t' | b'\r' => result.push(byte),
+            0x00..=0x1F | 0x7F => {}
+            // Copy UTF-8 characters whole, so a `0x9B` continuation byte is not seen as a CSI
+            0xC2..=0xF4 => {
+                let width = match byte {
+                    0xC2..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    _ => 4,
+                };
+                let char = &body[index - 1..(index - 1 + width).min(body.len())];
+                if std::str::from_utf8(char).is_ok() {
+                    result.extend_from_slice(char);
+                    index += width - 1;
+                } else {
+                    result.push(byte);
+                }
+            }
+            _ => result.push(byte),
+        }
+    }
+
+    if has_sgr {
+        result.extend_from_slice(RESET.as_bytes());
+    }
+    if newline {
+        result.push(b'\n');
+    }
+    result
+}
+
+/// Skips a CSI sequence with parameters starting at `index` and returns the index after it.
+///
+/// SGR sequences are written to `result`. A final byte that is not ASCII is not part of the
+/// sequence, it's kept.
+fn skip_csi(body: &[u8], mut index: usize, result: &mut Vec<u8>, has_sgr: &mut bool) -> usize {
+    let start = index;
+    while body.get(index).is_some_and(|b| (0x20..=0x3F).contains(b)) {
+        index += 1;
+    }
+    let Some(&last) = body.get(index).filter(|b| (0x40..=0x7E).contains(*b)) else {
+        return index;
+    };
+    let params = &body[start..index];
+    if last == b'm' && params.iter().all(|b| b.is_ascii_digit() || *b == b';') {
+        *has_sgr = true;
+        result.extend_from_slice(b"\x1B[");
+        result.extend_from_slice(params);
+        result.push(b'm');
+    }
+    index + 1
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_sanitize_line() {
+        let sanitize = |line: &str| String::from_utf8(sanitize_line(line.as_bytes())).unwrap();
+
+        assert_eq!("plain\n", sanitize("plain\n"));
+        assert_eq!("no newline", sanitize("no newline"));
+        assert_eq!("tab\there\r\n", sanitize("tab\there\r\n"));
+
+        // Colors are kept and reset at the end of the line
+        assert_eq!("\x1B[0;31mred\x1B[0m\n", sanitize("\x1B[0;31mred\n"));
+        assert_eq!("\x1B[1mbold\x1B[0m\x1B[0m", sanitize("\x1B[1mbold\x1B[0m"));
+
+        // Cursor movement, screen clearing and private modes are removed
+        assert_eq!("cleared\n", sanitize("\x1B[2J\x1B[Hcleared\n"));
+        assert_eq!("up\n", sanitize("\x1B[1Aup\x1B[K\n"));
+        assert_eq!("hidden\n", sanitize("\x1B[?25lhidden\n"));
+        assert_eq!("reset\n", sanitize("\x1Bcreset\n"));
+        assert_eq!("charset\n", sanitize("\x1B(Bcharset\n"));
+
+        // OSC window titles and hyperlinks are removed
+        assert_eq!("title\n", sanitize("\x1B]0;pwned\x07title\n"));
+        assert_eq!(
+            "link\n",
+            sanitize("\x1B]8;;https://example.com\x1B\\link\x1B]8;;\x1B\\\n")
+        );
+
+        // Other control characters are removed
+        assert_eq!("bell\n", sanitize("\x07bell\x08\n"));
+
+        // Truncated sequences at the end of a line are removed
+        assert_eq!("cut\n", sanitize("cut\x1B[12\n"));
+        assert_eq!("cut\n", sanitize("cut\x1B\n"));
+
+        // A character that is not ASCII ends the sequence and is kept
+        assert_eq!("équipe\n", sanitize("\x1Béquipe\n"));
+        assert_eq!("équipe\n", sanitize("\x1B[1équipe\n"));
+
+        // The single byte CSI is handled like `ESC [`, unless it's part of a character
+        let sanitize_bytes = |line: &[u8]| String::from_utf8(sanitize_line(line)).unwrap();
+        assert_eq!("cleared\n", sanitize_bytes(b"\x9B2Jcleared\n"));
+        assert_eq!("\x1B[31mred\x1B[0m\n", sanitize_bytes(b"\x9B31mred\n"));
+        assert_eq!("Û\n", sanitize("Û\n"));
+    }
+
     #[test]
     fn test_strip_ansi() {
         for color in [
@@ -116,6 +320,23 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_auto_enabled() {
+        let set = |value: &str| Some(OsString::from(value));
+
+        assert!(auto_enabled(None, None, None));
+        assert!(auto_enabled(None, None, set("xterm-256color")));
+        assert!(!auto_enabled(None, None, set("dumb")));
+
+        assert!(!auto_enabled(set("1"), None, None));
+        assert!(auto_enabled(set(""), None, None));
+        assert!(!auto_enabled(set("1"), set("1"), None));
+
+        assert!(auto_enabled(None, set("1"), set("dumb")));
+        assert!(!auto_enabled(None, set("0"), set("dumb")));
+        assert!(!auto_enabled(None, set(""), set("dumb")));
+    }
+
     #[test]
     fn empty_line() {
         let actual = wrap_ansi_escape_each_line(&ANSI::Red, "\n");
@@ -0,0 +1,449 @@
+//! Streaming and timer support for async code, enabled with the `tokio` feature.
+use crate::background_printer::Driver;
+use crate::line_filter::LineFilter;
+use crate::print_error::{PrintError, PrintResult};
+use crate::util::Source;
+use crate::{state, Clock, Print, SectionParent, StreamOptions, TimerOptions};
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// The writer type passed to the closure of [`Print::stream_with_async`].
+pub type AsyncStreamWriter = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+/// Writes are performed synchronously on the underlying writer, the same as the
+/// [`std::io::Write`] implementation. This matches how `std::io::stdout()` is usually
+/// written from async code.
+impl<W, P, C> AsyncWrite for Print<state::Stream<W, P>, C>
+where
+    W: Write + Unpin,
+    C: Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.get_mut().state.write.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.get_mut().state.write.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<W, P, C> Print<state::SubBullet<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    /// Like [`Print::stream_with`], but for async code such as a `tokio::process::Command`.
+    ///
+    /// The closure receives two [`tokio::io::AsyncWrite`] writers, usually for stdout and
+    /// stderr, and returns a future. Output written to them is formatted the same way as
+    /// [`Print::stream_with`]. The writers must be dropped by the time the future completes,
+    /// do not return them from it.
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    /// use tokio::io::AsyncWriteExt;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut output = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Streaming");
+    ///
+    /// let result = output
+    ///     .stream_with_async("Running `echo`", |mut stdout, _stderr| async move {
+    ///         stdout.write_all(b"hello world\n").await
+    ///     })
+    ///     .await;
+    ///
+    /// result.unwrap();
+    /// output.done().done();
+    /// # }
+    /// ```
+    #[allow(clippy::missing_panics_doc)]
+    pub async fn stream_with_async<F, Fut, T>(&mut self, s: impl AsRef<str>, f: F) -> T
+    where
+        F: FnOnce(AsyncStreamWriter, AsyncStreamWriter) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.try_stream_with_async(s, f)
+            .await
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::stream_with_async`], but returns a [`PrintError`] instead of panicking
+    /// when the output cannot be written.
+    ///
+    /// The future is always awaited, even when the output cannot be written. Its output is
+    /// available from the error via [`PrintError::into_inner`].
+    pub async fn try_stream_with_async<F, Fut, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        f: F,
+    ) -> Result<T, PrintError<T>>
+    where
+        F: FnOnce(AsyncStreamWriter, AsyncStreamWriter) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.try_stream_with_options_async(s, StreamOptions::default(), f)
+            .await
+    }
+
+    /// Like [`Print::stream_with_async`], but processes the streamed output according to the
+    /// given [`StreamOptions`].
+    #[allow(clippy::missing_panics_doc)]
+    pub async fn stream_with_options_async<F, Fut, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce(AsyncStreamWriter, AsyncStreamWriter) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.try_stream_with_options_async(s, options, f)
+            .await
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::stream_with_options_async`], but returns a [`PrintError`] instead of
+    /// panicking when the output cannot be written.
+    pub async fn try_stream_with_options_async<F, Fut, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        f: F,
+    ) -> Result<T, PrintError<T>>
+    where
+        F: FnOnce(AsyncStreamWriter, AsyncStreamWriter) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut result = self.start_stream_with(s);
+        let started = self.clock.now();
+
+        let filter = LineFilter::new(&options);
+        let (sender, mut receiver) = unbounded_channel::<(Source, Vec<u8>)>();
+        let writer = |sender, source| -> AsyncStreamWriter {
+            Box::new(SyncWrite(Self::map_stream_writer(
+                self.secrets.clone(),
+                ChannelWriter { sender, source },
+                options.clone(),
+            )))
+        };
+        let value = f(
+            writer(sender.clone(), Source::Stdout),
+            writer(sender, Source::Stderr),
+        );
+
+        let mut heartbeat = self.heartbeat(&options);
+        let stdout_prefix = self.stream_prefix(&options, Source::Stdout);
+        let stderr_prefix = self.stream_prefix(&options, Source::Stderr);
+        let write_result = &mut result;
+        let write = &mut self.state.write;
+        let filter = &filter;
+        let receive = async move {
+            // Exits once both writers are dropped. After a write error, keep receiving so the
+            // writers never observe a closed channel.
+            loop {
+                let message = match heartbeat.as_mut() {
+                    None => receiver.recv().await,
+                    Some(heartbeat) => loop {
+                        let (line, wait) = heartbeat.poll();
+                        if let (Some(line), Ok(())) = (line, &write_result) {
+                            *write_result = write.write_all(line.as_bytes());
+                        }
+                        if let Ok(message) = tokio::time::timeout(wait, receiver.recv()).await {
+                            heartbeat.reset();
+                            break message;
+                        }
+                    },
+                };
+                let Some((source, message)) = message else {
+                    break;
+                };
+                let prefix = match source {
+                    Source::Stdout => &stdout_prefix,
+                    Source::Stderr => &stderr_prefix,
+                };
+                let line = Self::format_stream_line(message, prefix, filter);
+                if write_result.is_ok() {
+                    *write_result = write.write_all(&line);
+                }
+            }
+        };
+        let (value, ()) = tokio::join!(value, receive);
+
+        self.finish_stream_with(started, filter, &mut result);
+        PrintError::from_io(value, result)
+    }
+
+    /// Like [`Print::start_timer`], but ticks on the current tokio runtime instead of a
+    /// dedicated thread.
+    ///
+    /// The output is the same, and the timer is stopped the same way via [`Print::done`] or
+    /// [`Print::cancel`]. When called outside of a tokio runtime, it falls back to a thread.
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let timer = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Ruby version")
+    ///     .start_tokio_timer("Installing");
+    ///
+    /// tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    ///
+    /// timer.done().done().done();
+    /// # }
+    /// ```
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn start_tokio_timer(self, s: impl AsRef<str>) -> Print<state::Background<W, P>, C> {
+        self.try_start_tokio_timer(s)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_tokio_timer`], but returns a [`PrintError`] instead of panicking
+    /// when the output cannot be written.
+    pub fn try_start_tokio_timer(
+        self,
+        s: impl AsRef<str>,
+    ) -> PrintResult<state::Background<W, P>, C> {
+        self.try_start_tokio_timer_with_options(s, TimerOptions::default())
+    }
+
+    /// Like [`Print::start_tokio_timer`], but the ticks are printed according to the given
+    /// [`TimerOptions`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn start_tokio_timer_with_options(
+        self,
+        s: impl AsRef<str>,
+        options: TimerOptions,
+    ) -> Print<state::Background<W, P>, C> {
+        self.try_start_tokio_timer_with_options(s, options)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_tokio_timer_with_options`], but returns a [`PrintError`] instead of
+    /// panicking when the output cannot be written.
+    pub fn try_start_tokio_timer_with_options(
+        self,
+        s: impl AsRef<str>,
+        options: TimerOptions,
+    ) -> PrintResult<state::Background<W, P>, C> {
+        self.try_start_timer_with_driver(s, &options, Driver::Tokio)
+    }
+}
+
+/// Sends each write over a channel to be written by the receiver, tagged with its source.
+struct ChannelWriter {
+    sender: UnboundedSender<(Source, Vec<u8>)>,
+    source: Source,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sender
+            .send((self.source, buf.to_vec()))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Adapts a non-blocking [`std::io::Write`] to [`AsyncWrite`].
+struct SyncWrite<W>(W);
+
+impl<W: Write + Unpin> AsyncWrite for SyncWrite<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.get_mut().0.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.get_mut().0.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::strip_ansi;
+    use crate::testing::{print, Capture, ManualClock};
+    use crate::{Print, TimerOptions};
+    use indoc::formatdoc;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn async_write_stream() {
+        let mut stream = Print::new(Vec::new())
+            .without_header()
+            .bullet("Streaming")
+            .start_stream("Running");
+        stream.write_all(b"hello\nworld\n").await.unwrap();
+        let io = stream.done().done().done();
+
+        let expected = formatdoc! {"
+            - Streaming
+              - Running
+
+                  hello
+                  world
+
+              - Done (< 0.1s)
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
+    #[tokio::test]
+    async fn stream_with_async() {
+        let mut output = Print::new(Vec::new()).without_header().bullet("Streaming");
+
+        let value = output
+            .stream_with_async("Running", |mut stdout, mut stderr| async move {
+                stdout.write_all(b"hello ").await.unwrap();
+                stdout.write_all(b"world\n").await.unwrap();
+                tokio::task::yield_now().await;
+                stderr.write_all(b"oops\n").await.unwrap();
+                42
+            })
+            .await;
+        assert_eq!(42, value);
+
+        let mut process = output
+            .stream_with_async("Running `echo`", |mut stdout, _| async move {
+                let out = tokio::process::Command::new("echo")
+                    .arg("from a process")
+                    .output()
+                    .await
+                    .unwrap();
+                stdout.write_all(&out.stdout).await.unwrap();
+                out.status
+            })
+            .await;
+        assert!(process.success());
+        process = output
+            .stream_with_async("Again", |_, _| async move { process })
+            .await;
+        assert!(process.success());
+
+        let io = output.done().done();
+        let expected = formatdoc! {"
+            - Streaming
+              - Running
+
+                  hello world
+                  oops
+
+              - Done (< 0.1s)
+              - Running `echo`
+
+                  from a process
+
+              - Done (< 0.1s)
+              - Again
+
+              - Done (< 0.1s)
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
+    #[tokio::test]
+    async fn tokio_timer_ticks_on_the_runtime() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let timer = print(&capture, &clock)
+            .without_header()
+            .bullet("Ruby version")
+            .start_tokio_timer("Installing");
+        clock.advance(Duration::from_secs(2));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let before_done = capture.contents();
+        timer.done().done().done();
+
+        // Ticks are printed by the task while the timer runs
+        assert_eq!("- Ruby version\n  - Installing ....", before_done);
+        assert_eq!(
+            formatdoc! {"
+                - Ruby version
+                  - Installing ..... (2.0s)
+                - Done (finished in 2.0s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[tokio::test]
+    async fn tokio_timer_with_options() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let timer = print(&capture, &clock)
+            .without_header()
+            .bullet("Ruby version")
+            .start_tokio_timer_with_options("Installing", TimerOptions::new().tick("+"));
+        clock.advance(Duration::from_secs(2));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        timer.done().done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Ruby version
+                  - Installing +++++ (2.0s)
+                - Done (finished in 2.0s)
+            "},
+            strip_ansi(capture.contents())
+        );
+    }
+
+    #[test]
+    fn tokio_timer_without_a_runtime() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let timer = print(&capture, &clock)
+            .without_header()
+            .bullet("Ruby version")
+            .start_tokio_timer("Installing");
+        clock.advance(Duration::from_secs(1));
+        timer.cancel("Interrupted").done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Ruby version
+                  - Installing .... (Interrupted)
+                - Done (finished in 1.0s)
+            "},
+            capture.contents()
+        );
+    }
+}
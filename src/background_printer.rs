@@ -1,49 +1,261 @@
 //! This module is responsible for the logic involved in the printing to output while
 //! other work is being performed. Such as printing dots while a download is being performed.
+use crate::Clock;
 use std::io::Write;
-use std::sync::mpsc::{channel, Sender};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// Repeatedly prints `tick` to the given buffer at the given interval. The `start` argument will be printed before the first `tick` and the `end` argument will be printed after the last `tick` when the timer is stopped.
+/// What drives the ticks of a background printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Driver {
+    /// A dedicated OS thread.
+    #[default]
+    Thread,
+    /// A task on the current tokio runtime. Falls back to a thread when there is no runtime.
+    #[cfg(feature = "tokio")]
+    Tokio,
+}
+
+/// Repeatedly prints the output of `tick` to the given buffer according to the `schedule`. The `start` argument will be printed before the first `tick` and the `end` argument will be printed after the last `tick` when the timer is stopped.
+///
+/// Ticks are derived from the clock. The `schedule` returns the time since the start at which
+/// a tick is due, given the number of the tick (starting at zero). It must return increasing
+/// values, see [`every`]. The `tick` function receives the time the tick was due. Any ticks
+/// that are due but have not been printed yet are printed when the timer is stopped, so the
+/// total is exact even when the clock is advanced manually.
+///
+/// If writing to the buffer fails, the printer stops writing and the error is returned
+/// alongside the buffer when the timer is stopped.
 ///
 /// Returns a struct that allows for manually stopping the timer or will automatically stop
 /// the timer if the guard is dropped. This functionality allows for errors that trigger
 /// an exit of the function to not accidentally have a timer printing in the background
 /// forever.
 #[must_use]
-pub(crate) fn print_interval<W>(
-    mut buffer: W,
-    interval: Duration,
+pub(crate) fn print_interval<W, C>(
+    buffer: W,
+    clock: C,
+    schedule: impl Fn(u32) -> Duration + Send + 'static,
     start: String,
-    tick: String,
+    tick: impl FnMut(Duration) -> String + Send + 'static,
     end: String,
+    driver: Driver,
 ) -> PrintGuard<W>
+where
+    W: Write + Send + 'static,
+    C: Clock,
+{
+    let ticker = Ticker::new(
+        buffer,
+        clock,
+        Box::new(schedule),
+        start,
+        Box::new(tick),
+        end,
+    );
+    match driver {
+        Driver::Thread => spawn_thread(ticker),
+        #[cfg(feature = "tokio")]
+        Driver::Tokio => match tokio::runtime::Handle::try_current() {
+            Ok(handle) => spawn_task(&handle, ticker),
+            Err(_) => spawn_thread(ticker),
+        },
+    }
+}
+
+/// A schedule with a tick when the printer starts and one after every interval.
+///
+/// Intervals shorter than a millisecond are treated as a millisecond.
+pub(crate) fn every(interval: Duration) -> impl Fn(u32) -> Duration + Send + 'static {
+    let interval = interval.max(Duration::from_millis(1));
+    move |tick| interval.saturating_mul(tick)
+}
+
+fn spawn_thread<W>(mut ticker: Ticker<W>) -> PrintGuard<W>
 where
     W: Write + Send + 'static,
 {
     let (sender, receiver) = channel::<()>();
 
     let join_handle = std::thread::spawn(move || {
-        write!(buffer, "{start}").expect("Writer should not be closed");
-        buffer.flush().expect("Writer should not be closed");
-
         loop {
-            write!(buffer, "{tick}").expect("Writer should not be closed");
-            buffer.flush().expect("Writer should not be closed");
+            let wait = ticker.print_due_ticks();
+            match receiver.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        ticker.finish()
+    });
+
+    PrintGuard::new(Stopper::Thread {
+        join_handle,
+        stop_signal: sender,
+    })
+}
+
+/// Ticks on the tokio runtime instead of a dedicated thread.
+///
+/// The ticker is shared with the guard. Stopping takes the ticker out of the mutex, after
+/// which the task can no longer write, so there's no need to wait for the task to finish.
+#[cfg(feature = "tokio")]
+fn spawn_task<W>(handle: &tokio::runtime::Handle, ticker: Ticker<W>) -> PrintGuard<W>
+where
+    W: Write + Send + 'static,
+{
+    let ticker = Arc::new(Mutex::new(Some(ticker)));
+    let task = handle.spawn({
+        let ticker = Arc::clone(&ticker);
+        async move {
+            loop {
+                let wait = match ticker
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .as_mut()
+                {
+                    Some(ticker) => ticker.print_due_ticks(),
+                    None => return,
+                };
+                tokio::time::sleep(wait).await;
+            }
+        }
+    });
+
+    PrintGuard::new(Stopper::Task { ticker, task })
+}
+
+/// Writes ticks for the elapsed time, shared by every [`Driver`].
+struct Ticker<W> {
+    buffer: W,
+    now: Box<dyn Fn() -> Instant + Send>,
+    started: Instant,
+    schedule: Box<dyn Fn(u32) -> Duration + Send>,
+    tick: Box<dyn FnMut(Duration) -> String + Send>,
+    end: String,
+    ticks: u32,
+    result: std::io::Result<()>,
+}
+
+impl<W: Write> Ticker<W> {
+    fn new<C: Clock>(
+        mut buffer: W,
+        clock: C,
+        schedule: Box<dyn Fn(u32) -> Duration + Send>,
+        start: String,
+        tick: Box<dyn FnMut(Duration) -> String + Send>,
+        end: String,
+    ) -> Self {
+        let started = clock.now();
+        let result = write!(buffer, "{start}");
+        // Print the first tick right away rather than whenever the driver gets to run first
+        let mut ticker = Self {
+            buffer,
+            now: Box::new(move || clock.now()),
+            started,
+            schedule,
+            tick,
+            end,
+            ticks: 0,
+            result,
+        };
+        ticker.print_due_ticks();
+        ticker
+    }
+
+    /// Print every tick that is due and return the time until the next one.
+    ///
+    /// After a write error nothing more is printed and the next tick is never due.
+    fn print_due_ticks(&mut self) -> Duration {
+        let elapsed = (self.now)().saturating_duration_since(self.started);
 
-            if receiver.recv_timeout(interval).is_ok() {
+        while self.result.is_ok() {
+            let due = (self.schedule)(self.ticks);
+            if due > elapsed {
                 break;
             }
+            self.result = write!(self.buffer, "{}", (self.tick)(due));
+            self.ticks = self.ticks.saturating_add(1);
+        }
+        if self.result.is_ok() {
+            self.result = self.buffer.flush();
         }
 
-        write!(buffer, "{end}").expect("Writer should not be closed");
-        buffer.flush().expect("Writer should not be closed");
+        if self.result.is_ok() {
+            (self.schedule)(self.ticks).saturating_sub(elapsed)
+        } else {
+            Duration::MAX
+        }
+    }
 
-        buffer
-    });
+    /// Print any remaining ticks and the end, then hand back the buffer.
+    fn finish(mut self) -> (W, std::io::Result<()>) {
+        self.print_due_ticks();
+        let result = self
+            .result
+            .and_then(|()| write!(self.buffer, "{}", self.end))
+            .and_then(|()| self.buffer.flush());
+
+        (self.buffer, result)
+    }
+}
 
-    PrintGuard::new(join_handle, sender)
+/// How to stop a running printer and get its buffer back.
+#[derive(Debug)]
+enum Stopper<W> {
+    Thread {
+        /// Holds the handle to the thread printing ticks in the background.
+        join_handle: JoinHandle<(W, std::io::Result<()>)>,
+
+        /// Holds the signaling method to tell the background printer
+        /// to stop emitting.
+        stop_signal: Sender<()>,
+    },
+    #[cfg(feature = "tokio")]
+    Task {
+        ticker: Arc<Mutex<Option<Ticker<W>>>>,
+        task: tokio::task::JoinHandle<()>,
+    },
+}
+
+impl<W: Write> Stopper<W> {
+    fn stop(self) -> std::thread::Result<(W, std::io::Result<()>)> {
+        match self {
+            Stopper::Thread {
+                join_handle,
+                stop_signal,
+            } => {
+                // Ignore if the channel is closed, likely means the thread died which
+                // we want in this case.
+                let _ = stop_signal.send(());
+                join_handle.join()
+            }
+            #[cfg(feature = "tokio")]
+            Stopper::Task { ticker, task } => {
+                task.abort();
+                // A panic while ticking does not leave the ticker in an invalid state
+                let ticker = ticker
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .take();
+                match ticker {
+                    Some(ticker) => Ok(ticker.finish()),
+                    None => Err(Box::new("Internal error: timer task already stopped")),
+                }
+            }
+        }
+    }
+}
+
+impl<W> std::fmt::Debug for Ticker<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ticker")
+            .field("ticks", &self.ticks)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Holds the reference to the background printer.
@@ -59,20 +271,16 @@ where
 /// Updates to this code need to take care to not introduce a panic. See
 /// documentation in `PrintGuard::stop` below for more details.
 #[derive(Debug)]
-pub(crate) struct PrintGuard<W> {
-    /// Holds the handle to the thread printing ticks in the background.
+pub(crate) struct PrintGuard<W: Write> {
+    /// Holds the means to stop the printer.
     ///
     /// Structs that implement `Drop` must ensure a valid internal state at
-    /// all times due to E0509. The handle is wrapped in an option to allow the
+    /// all times due to E0509. The stopper is wrapped in an option to allow the
     /// inner value to be removed while preserving internal state.
-    join_handle: Option<JoinHandle<W>>,
-
-    /// Holds the signaling method to tell the background printer
-    /// to stop emitting.
-    stop_signal: Sender<()>,
+    stopper: Option<Stopper<W>>,
 }
 
-impl<W> Drop for PrintGuard<W> {
+impl<W: Write> Drop for PrintGuard<W> {
     fn drop(&mut self) {
         // A note on correctness. It might seem that it's enough to signal the thread to
         // stop, that we don't also have to join and wait for it to finish, but that's not
@@ -82,27 +290,25 @@ impl<W> Drop for PrintGuard<W> {
         // as an error message if a global writer is being used such as stdout.
         // As a result we have to signal AND ensure the thread is stopped before
         // continuing.
-        if let Some(join_handle) = self.join_handle.take() {
-            let _ = self.stop_signal.send(());
-            let _ = join_handle.join();
+        if let Some(stopper) = self.stopper.take() {
+            let _ = stopper.stop();
         }
     }
 }
 
-impl<W> PrintGuard<W> {
+impl<W: Write> PrintGuard<W> {
     /// Preserve internal state by ensuring the `Option` is always populated
-    fn new(join_handle: JoinHandle<W>, sender: Sender<()>) -> Self {
+    fn new(stopper: Stopper<W>) -> Self {
         let guard = PrintGuard {
-            join_handle: Some(join_handle),
-            stop_signal: sender,
+            stopper: Some(stopper),
         };
-        debug_assert!(guard.join_handle.is_some());
+        debug_assert!(guard.stopper.is_some());
 
         guard
     }
 
     /// The only thing a consumer can do is stop the background printer and receive
-    /// the original buffer.
+    /// the original buffer along with the result of writing to it.
     ///
     /// # Panics
     ///
@@ -126,14 +332,9 @@ impl<W> PrintGuard<W> {
     /// the code is exercised under test, and exhaustively document why this panic
     /// exists and how developers working with this code can maintain safety.
     #[allow(clippy::panic_in_result_fn)]
-    pub(crate) fn stop(mut self) -> std::thread::Result<W> {
-        // Ignore if the channel is closed, likely means the thread died which
-        // we want in this case.
-        match self.join_handle.take() {
-            Some(join_handle) => {
-                let _ = self.stop_signal.send(());
-                join_handle.join()
-            }
+    pub(crate) fn stop(mut self) -> std::thread::Result<(W, std::io::Result<()>)> {
+        match self.stopper.take() {
+            Some(stopper) => stopper.stop(),
             None => panic!("Internal error: Dot print internal state should never be None"),
         }
     }
@@ -142,6 +343,8 @@ impl<W> PrintGuard<W> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::util::ClosedWriter;
+    use crate::ManualClock;
     use std::fs::{File, OpenOptions};
     use tempfile::NamedTempFile;
 
@@ -152,12 +355,15 @@ mod test {
 
         let dot = print_interval(
             buffer,
-            Duration::from_millis(1),
+            ManualClock::new(),
+            every(Duration::from_millis(1)),
             String::from(" ."),
-            String::from("."),
+            |_| String::from("."),
             String::from(". "),
+            Driver::Thread,
         );
-        let mut writer = dot.stop().unwrap();
+        let (mut writer, result) = dot.stop().unwrap();
+        result.unwrap();
 
         write!(writer, "after").unwrap();
         writer.flush().unwrap();
@@ -173,10 +379,12 @@ mod test {
 
         let dot = print_interval(
             log,
-            Duration::from_millis(1),
+            ManualClock::new(),
+            every(Duration::from_millis(1)),
             String::from(" ."),
-            String::from("."),
+            |_| String::from("."),
             String::from(". "),
+            Driver::Thread,
         );
         drop(dot);
 
@@ -192,4 +400,39 @@ mod test {
             std::fs::read_to_string(tempfile.path()).unwrap()
         );
     }
+
+    #[test]
+    fn ticks_follow_the_clock() {
+        let clock = ManualClock::new();
+        let dot = print_interval(
+            Vec::new(),
+            clock.clone(),
+            every(Duration::from_secs(1)),
+            String::from(" ."),
+            |_| String::from("."),
+            String::from(". "),
+            Driver::Thread,
+        );
+        clock.advance(Duration::from_millis(2_500));
+        let (writer, result) = dot.stop().unwrap();
+        result.unwrap();
+
+        assert_eq!(" ..... ", String::from_utf8_lossy(&writer));
+    }
+
+    #[test]
+    fn closed_writer_does_not_panic() {
+        let dot = print_interval(
+            ClosedWriter,
+            crate::SystemClock,
+            every(Duration::from_millis(1)),
+            String::from(" ."),
+            |_| String::from("."),
+            String::from(". "),
+            Driver::Thread,
+        );
+        let (_, result) = dot.stop().unwrap();
+
+        assert_eq!(std::io::ErrorKind::BrokenPipe, result.unwrap_err().kind());
+    }
 }
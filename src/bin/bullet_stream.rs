@@ -0,0 +1,8 @@
+//! Emit `bullet_stream` formatted output from shell scripts.
+//!
+//! Run `bullet_stream --help` for usage.
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    bullet_stream::cli::run(std::env::args().skip(1))
+}
@@ -0,0 +1,44 @@
+/// Formats a number of bytes with decimal units, for example `2.5 MB`.
+pub(crate) fn human(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        // Compare the value as it will be printed, so 999,950 bytes is `1 MB` and not `1000 KB`
+        if (value * 10.0).round() / 10.0 < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+
+    let value = format!("{value:.1}");
+    let value = value.strip_suffix(".0").unwrap_or(&value);
+    format!("{value} {unit}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_bytes() {
+        assert_eq!(human(0), "0 B");
+        assert_eq!(human(999), "999 B");
+        assert_eq!(human(1000), "1 KB");
+        assert_eq!(human(1_550), "1.6 KB");
+        assert_eq!(human(999_949), "999.9 KB");
+        assert_eq!(human(999_950), "1 MB");
+        assert_eq!(human(999_950_000), "1 GB");
+        assert_eq!(human(5_000_000), "5 MB");
+        assert_eq!(human(2_500_000), "2.5 MB");
+        assert_eq!(human(3_000_000_000), "3 GB");
+        assert_eq!(human(7_000_000_000_000_000), "7000 TB");
+    }
+}
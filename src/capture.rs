@@ -0,0 +1,297 @@
+//! Keeping a copy of streamed output, see [`crate::Print::stream_with_capture`].
+use crate::print_error::PrintError;
+use crate::{state, Clock, Print, SectionParent, StreamOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The output captured by [`crate::Print::stream_with_capture`].
+///
+/// The output is kept as it was written by the command, before any [`crate::StreamOptions`]
+/// are applied. Secrets added with [`crate::Print::redact`] are replaced with `[REDACTED]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl CapturedOutput {
+    /// The bytes written to the first writer passed to the closure.
+    #[must_use]
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// The bytes written to the second writer passed to the closure.
+    #[must_use]
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Returns the captured stdout and stderr.
+    #[must_use]
+    pub fn into_parts(self) -> (Vec<u8>, Vec<u8>) {
+        (self.stdout, self.stderr)
+    }
+}
+
+impl<W, P, C> Print<state::SubBullet<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    /// Like [`Print::stream_with_options`], but also returns a copy of the output.
+    ///
+    /// Use it to show the end of the output in an error message when the command fails,
+    /// without running it again. To only keep the last lines of long output, set
+    /// [`StreamOptions::max_captured_lines`].
+    ///
+    /// ```no_run
+    /// use bullet_stream::{style, Print, StreamOptions};
+    /// use fun_run::CommandWithName;
+    /// use std::process::Command;
+    ///
+    /// let mut output = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Installing");
+    ///
+    /// let mut cmd = Command::new("bundle");
+    /// cmd.arg("install");
+    ///
+    /// let (result, captured) = output.stream_with_capture(
+    ///     format!("Running {}", style::command(cmd.name())),
+    ///     StreamOptions::new().max_captured_lines(20),
+    ///     |stdout, stderr| cmd.stream_output(stdout, stderr),
+    /// );
+    /// if result.is_err() {
+    ///     output.done().error(format!(
+    ///         "Command failed, last lines of output:\n\n{}",
+    ///         String::from_utf8_lossy(captured.stderr())
+    ///     ));
+    /// }
+    /// ```
+    #[allow(clippy::missing_panics_doc)]
+    pub fn stream_with_capture<F, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        f: F,
+    ) -> (T, CapturedOutput)
+    where
+        F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
+        T: 'static,
+    {
+        self.try_stream_with_capture(s, options, f)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::stream_with_capture`], but returns a [`PrintError`] instead of panicking
+    /// when the output cannot be written.
+    ///
+    /// The closure is always called, even when the output cannot be written. Its return value
+    /// and the captured output are available from the error via [`PrintError::into_inner`].
+    pub fn try_stream_with_capture<F, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        mut f: F,
+    ) -> Result<(T, CapturedOutput), PrintError<(T, CapturedOutput)>>
+    where
+        F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
+        T: 'static,
+    {
+        let stdout = CaptureBuffer::new(options.max_captured_lines);
+        let stderr = CaptureBuffer::new(options.max_captured_lines);
+        let result = self.try_stream_with_options(s, options, |out, err| {
+            f(Box::new(stdout.writer(out)), Box::new(stderr.writer(err)))
+        });
+
+        // Redacted as a whole, a secret can be split over several writes
+        let captured = CapturedOutput {
+            stdout: self.secrets.redact_bytes(stdout.take()),
+            stderr: self.secrets.redact_bytes(stderr.take()),
+        };
+        match result {
+            Ok(value) => Ok((value, captured)),
+            Err(error) => {
+                let (value, source) = error.into_parts();
+                Err(PrintError::new((value, captured), source))
+            }
+        }
+    }
+}
+
+/// A buffer shared with a [`CaptureWrite`], that keeps at most `max_lines` lines when given.
+#[derive(Debug, Clone)]
+pub(crate) struct CaptureBuffer {
+    bytes: Arc<Mutex<Vec<u8>>>,
+    max_lines: Option<usize>,
+}
+
+impl CaptureBuffer {
+    pub(crate) fn new(max_lines: Option<usize>) -> Self {
+        Self {
+            bytes: Arc::new(Mutex::new(Vec::new())),
+            max_lines,
+        }
+    }
+
+    /// A writer that copies everything written to it into this buffer before passing it on.
+    pub(crate) fn writer<W: Write>(&self, inner: W) -> CaptureWrite<W> {
+        CaptureWrite {
+            inner,
+            buffer: self.clone(),
+        }
+    }
+
+    fn push(&self, buf: &[u8]) {
+        let mut bytes = self.bytes.lock().unwrap_or_else(PoisonError::into_inner);
+        bytes.extend_from_slice(buf);
+        if let Some(max_lines) = self.max_lines {
+            keep_last_lines(&mut bytes, max_lines);
+        }
+    }
+
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.bytes.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Writes to the inner writer and copies the written bytes to a [`CaptureBuffer`].
+pub(crate) struct CaptureWrite<W> {
+    inner: W,
+    buffer: CaptureBuffer,
+}
+
+impl<W: Write> Write for CaptureWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.buffer.push(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Remove lines from the front until at most `max_lines` are left. A trailing line without a
+/// newline counts as a line.
+fn keep_last_lines(bytes: &mut Vec<u8>, max_lines: usize) {
+    if max_lines == 0 {
+        bytes.clear();
+        return;
+    }
+    let end = if bytes.last() == Some(&b'\n') {
+        bytes.len() - 1
+    } else {
+        bytes.len()
+    };
+    let start = bytes[..end]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, byte)| **byte == b'\n')
+        .nth(max_lines - 1)
+        .map(|(index, _)| index + 1);
+    if let Some(start) = start {
+        bytes.drain(..start);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_keep_last_lines() {
+        let keep = |input: &str, max_lines| {
+            let mut bytes = input.as_bytes().to_vec();
+            keep_last_lines(&mut bytes, max_lines);
+            String::from_utf8(bytes).unwrap()
+        };
+        assert_eq!("b\nc\n", keep("a\nb\nc\n", 2));
+        assert_eq!("b\nc", keep("a\nb\nc", 2));
+        assert_eq!("a\nb\nc\n", keep("a\nb\nc\n", 3));
+        assert_eq!("c\n", keep("a\nb\nc\n", 1));
+        assert_eq!("", keep("a\nb\nc\n", 0));
+        assert_eq!("", keep("", 2));
+    }
+
+    #[test]
+    fn capture_write_copies_output() {
+        let buffer = CaptureBuffer::new(Some(1));
+        let mut output = Vec::new();
+        let mut writer = buffer.writer(&mut output);
+        write!(writer, "hello\nworld\n").unwrap();
+
+        assert_eq!(b"hello\nworld\n".to_vec(), output);
+        assert_eq!(b"world\n".to_vec(), buffer.take());
+    }
+
+    #[test]
+    fn stream_with_capture_returns_output() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let mut output = print(&capture, &clock)
+            .without_header()
+            .bullet("Installing");
+        let (value, captured) = output.stream_with_capture(
+            "Running `bundle install`",
+            StreamOptions::new().max_captured_lines(2),
+            |mut stdout, mut stderr| {
+                write!(stdout, "one\ntwo\nthree\n").unwrap();
+                writeln!(stderr, "error: failed").unwrap();
+                1
+            },
+        );
+        output.done().done();
+
+        assert_eq!(1, value);
+        assert_eq!(b"two\nthree\n", captured.stdout());
+        assert_eq!(b"error: failed\n", captured.stderr());
+        assert_eq!(
+            formatdoc! {"
+                - Installing
+                  - Running `bundle install`
+
+                      one
+                      two
+                      three
+                      error: failed
+
+                  - Done (< 0.1s)
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn captured_output_is_redacted() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let mut output = print(&capture, &clock)
+            .redact("hunter2")
+            .without_header()
+            .bullet("Installing");
+        let ((), captured) = output.stream_with_capture(
+            "Running `bundle install`",
+            StreamOptions::new(),
+            |mut stdout, mut stderr| {
+                write!(stdout, "token: hun").unwrap();
+                writeln!(stdout, "ter2").unwrap();
+                writeln!(stderr, "error: hunter2 rejected").unwrap();
+            },
+        );
+        output.done().done();
+
+        assert_eq!(b"token: [REDACTED]\n", captured.stdout());
+        assert_eq!(b"error: [REDACTED] rejected\n", captured.stderr());
+        assert!(!format!("{captured:?}").contains("hunter2"));
+    }
+}
@@ -0,0 +1,524 @@
+//! Implementation of the `bullet_stream` command line binary.
+//!
+//! Each invocation of the binary performs a single transition. The state needed to produce
+//! output that is byte-for-byte identical to the [`Print`] API (the current state, the
+//! number of trailing newlines emitted, and when the build started) is persisted to a state
+//! file between invocations.
+//!
+//! The state file location is read from the `BULLET_STREAM_STATE` environment variable. On
+//! Unix it defaults to a file in the temp directory named after the parent process ID so that
+//! every invocation from the same shell script shares state.
+use crate::util::ParagraphInspectWrite;
+use crate::{state, style, Print};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "\
+Usage: bullet_stream <COMMAND> [ARGS]
+
+Commands:
+  h1 <text>                      Announce the start of the build
+  h2 <text>                      Announce the start of the build or a new section of it
+  bullet <text>                  Start a top-level bullet point section
+  sub-bullet <text>              Emit a step within the current section
+  warning <text>                 Emit a warning paragraph
+  important <text>               Emit an important paragraph
+  error <text>                   Emit an error paragraph and end the build
+  stream [<text>] -- <cmd>...    Run a command and stream its output
+  timer [<text>] -- <cmd>...     Run a command and print dots while it runs
+  done                           Finish the build
+
+The state between invocations is stored in the file at $BULLET_STREAM_STATE.
+";
+
+/// Runs the CLI with the given arguments (excluding the binary name), writing to stdout.
+#[must_use]
+pub fn run(args: impl IntoIterator<Item = String>) -> ExitCode {
+    let args = args.into_iter().collect::<Vec<String>>();
+    if is_help(&args) {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let Some(path) = state_path() else {
+        eprintln!("bullet_stream: BULLET_STREAM_STATE must be set to a file path");
+        return ExitCode::from(2);
+    };
+
+    match run_with(&args, &path, std::io::stdout()) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("bullet_stream: {error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Only the first argument is checked, later ones may belong to the command being run, as in
+/// `bullet_stream stream -- ls -h`.
+fn is_help(args: &[String]) -> bool {
+    matches!(
+        args.first().map(String::as_str),
+        None | Some("--help" | "-h")
+    )
+}
+
+fn state_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("BULLET_STREAM_STATE") {
+        Some(PathBuf::from(path))
+    } else {
+        default_state_path()
+    }
+}
+
+#[cfg(unix)]
+fn default_state_path() -> Option<PathBuf> {
+    let ppid = std::os::unix::process::parent_id();
+    Some(std::env::temp_dir().join(format!("bullet_stream_{ppid}.state")))
+}
+
+#[cfg(not(unix))]
+fn default_state_path() -> Option<PathBuf> {
+    None
+}
+
+/// Where in the [`Print`] state machine the previous invocation left off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Header,
+    Bullet,
+    SubBullet,
+}
+
+/// Everything that must survive between invocations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SavedState {
+    position: Position,
+    newlines_since_last_char: usize,
+    started_ms: Option<u128>,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {
+            position: Position::Header,
+            newlines_since_last_char: 0,
+            started_ms: None,
+        }
+    }
+}
+
+impl SavedState {
+    fn read(path: &Path) -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(error) => return Err(format!("Cannot read {}: {error}", path.display())),
+        };
+
+        let mut saved = Self::default();
+        for line in contents.lines() {
+            let invalid = || format!("Invalid line in {}: {line:?}", path.display());
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "position" => {
+                    saved.position = match value {
+                        "header" => Position::Header,
+                        "bullet" => Position::Bullet,
+                        "sub_bullet" => Position::SubBullet,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "newlines" => {
+                    saved.newlines_since_last_char = value.parse().map_err(|_| invalid())?
+                }
+                "started_ms" => saved.started_ms = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(saved)
+    }
+
+    fn write(&self, path: &Path) -> Result<(), String> {
+        let position = match self.position {
+            Position::Header => "header",
+            Position::Bullet => "bullet",
+            Position::SubBullet => "sub_bullet",
+        };
+        let mut contents = format!(
+            "position={position}\nnewlines={}\n",
+            self.newlines_since_last_char
+        );
+        if let Some(started_ms) = self.started_ms {
+            contents.push_str(&format!("started_ms={started_ms}\n"));
+        }
+        std::fs::write(path, contents)
+            .map_err(|error| format!("Cannot write {}: {error}", path.display()))
+    }
+
+    fn started(&self) -> Option<Instant> {
+        let elapsed = Duration::from_millis(
+            u64::try_from(now_ms().saturating_sub(self.started_ms?)).unwrap_or(u64::MAX),
+        );
+        Instant::now().checked_sub(elapsed)
+    }
+
+    fn restore<W: Write>(&self, write: &mut ParagraphInspectWrite<W>) {
+        write.newlines_since_last_char = self.newlines_since_last_char;
+        write.was_paragraph = self.newlines_since_last_char > 1;
+    }
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis()
+}
+
+/// The output, resumed from a saved state.
+enum Resumed<W: Write> {
+    Header(Print<state::Header<W>>),
+    Bullet(Print<state::Bullet<W>>),
+    SubBullet(Print<state::SubBullet<W>>),
+}
+
+impl<W> Resumed<W>
+where
+    W: Write + Send + Sync + 'static,
+{
+    /// Starts from [`Print::new`] so fields added to [`Print`] get their defaults here too.
+    fn new(saved: &SavedState, io: W) -> Self {
+        let mut output = Print::new(io);
+        saved.restore(&mut output.state.write);
+        let into_bullet = |output: Print<state::Header<W>>| {
+            let mut output = output.without_header();
+            output.started = saved.started();
+            output
+        };
+        match saved.position {
+            Position::Header => Resumed::Header(output),
+            Position::Bullet => Resumed::Bullet(into_bullet(output)),
+            Position::SubBullet => {
+                Resumed::SubBullet(into_bullet(output).map_state(|state| state::SubBullet {
+                    write: state.write,
+                    parent: PhantomData,
+                }))
+            }
+        }
+    }
+
+    fn save(&self, saved: &SavedState) -> SavedState {
+        let (position, write, started) = match self {
+            Resumed::Header(output) => (Position::Header, &output.state.write, output.started),
+            Resumed::Bullet(output) => (Position::Bullet, &output.state.write, output.started),
+            Resumed::SubBullet(output) => {
+                (Position::SubBullet, &output.state.write, output.started)
+            }
+        };
+        SavedState {
+            position,
+            newlines_since_last_char: write.newlines_since_last_char,
+            started_ms: match (saved.started_ms, started) {
+                (Some(started_ms), _) => Some(started_ms),
+                (None, Some(_)) => Some(now_ms()),
+                (None, None) => None,
+            },
+        }
+    }
+
+    /// Finish any open section so the output is ready for a new bullet.
+    fn into_bullet(self) -> Print<state::Bullet<W>> {
+        match self {
+            Resumed::Header(output) => output.without_header(),
+            Resumed::Bullet(output) => output,
+            Resumed::SubBullet(output) => output.done(),
+        }
+    }
+}
+
+/// Runs a single command against the state stored at `path`.
+fn run_with<W>(args: &[String], path: &Path, io: W) -> Result<ExitCode, String>
+where
+    W: Write + Send + Sync + 'static,
+{
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| String::from("Missing command"))?;
+    let text = rest.join(" ");
+    let saved = SavedState::read(path)?;
+    let output = Resumed::new(&saved, io);
+
+    // A command that cannot be run is reported after the state is saved, its output was
+    // already written
+    let mut exit_code = Ok(ExitCode::SUCCESS);
+    let next = match (command.as_str(), output) {
+        ("h1", Resumed::Header(output)) => Resumed::Bullet(output.h1(text)),
+        ("h2", Resumed::Header(output)) => Resumed::Bullet(output.h2(text)),
+        ("h2", output) => Resumed::Bullet(output.into_bullet().h2(text)),
+        ("bullet", output) => Resumed::SubBullet(output.into_bullet().bullet(text)),
+        ("sub-bullet", Resumed::SubBullet(output)) => Resumed::SubBullet(output.sub_bullet(text)),
+        ("warning", Resumed::Bullet(output)) => Resumed::Bullet(output.warning(text)),
+        ("warning", Resumed::SubBullet(output)) => Resumed::SubBullet(output.warning(text)),
+        ("important", Resumed::Bullet(output)) => Resumed::Bullet(output.important(text)),
+        ("important", Resumed::SubBullet(output)) => Resumed::SubBullet(output.important(text)),
+        ("error", Resumed::Bullet(output)) => return finish(path, || output.error(text)),
+        ("error", Resumed::SubBullet(output)) => return finish(path, || output.error(text)),
+        ("done", output @ (Resumed::Bullet(_) | Resumed::SubBullet(_))) => {
+            return finish(path, || {
+                output.into_bullet().done();
+            })
+        }
+        ("stream", Resumed::SubBullet(mut output)) => {
+            let (text, mut cmd) = parse_command(rest)?;
+            let status = output.stream_with(text, |mut stdout, mut stderr| {
+                let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+                let mut child_stdout = child.stdout.take();
+                let mut child_stderr = child.stderr.take();
+                std::thread::scope(|scope| {
+                    if let Some(child_stdout) = child_stdout.as_mut() {
+                        scope.spawn(|| std::io::copy(child_stdout, &mut stdout));
+                    }
+                    if let Some(child_stderr) = child_stderr.as_mut() {
+                        scope.spawn(|| std::io::copy(child_stderr, &mut stderr));
+                    }
+                });
+                child.wait()
+            });
+            exit_code = exit_code_for(status);
+            Resumed::SubBullet(output)
+        }
+        ("timer", Resumed::SubBullet(output)) => {
+            let (text, mut cmd) = parse_command(rest)?;
+            let timer = output.start_timer(text);
+            let status = cmd.stdout(Stdio::null()).stderr(Stdio::null()).status();
+            exit_code = exit_code_for(status);
+            Resumed::SubBullet(timer.done())
+        }
+        (
+            "h1" | "sub-bullet" | "warning" | "important" | "error" | "done" | "stream" | "timer",
+            output,
+        ) => {
+            let position = match output {
+                Resumed::Header(_) => "before a header (h1 or h2)",
+                Resumed::Bullet(_) => "outside of a bullet",
+                Resumed::SubBullet(_) => "inside of a bullet",
+            };
+            return Err(format!("Command `{command}` is not allowed {position}"));
+        }
+        _ => return Err(format!("Unknown command `{command}`\n\n{USAGE}")),
+    };
+
+    next.save(&saved).write(path)?;
+    exit_code
+}
+
+/// Ends the build, the state file is removed so the next build starts fresh.
+fn finish(path: &Path, f: impl FnOnce()) -> Result<ExitCode, String> {
+    f();
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(ExitCode::SUCCESS),
+        Err(error) => Err(format!("Cannot remove {}: {error}", path.display())),
+    }
+}
+
+/// Splits `[<text>] -- <cmd> [args]` into the message to display and the command to run.
+fn parse_command(args: &[String]) -> Result<(String, Command), String> {
+    let (text, cmd) = match args.iter().position(|arg| arg == "--") {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (&args[..0], args),
+    };
+    let (program, cmd_args) = cmd
+        .split_first()
+        .ok_or_else(|| String::from("Missing command to run after `--`"))?;
+
+    let text = if text.is_empty() {
+        let display = cmd.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>();
+        format!("Running {}", style::command(display.join(" ")))
+    } else {
+        text.join(" ")
+    };
+    let mut command = Command::new(program);
+    command.args(cmd_args);
+
+    Ok((text, command))
+}
+
+/// Quotes an argument for display when it would otherwise be split by a shell.
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"\\$`".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn exit_code_for(status: std::io::Result<std::process::ExitStatus>) -> Result<ExitCode, String> {
+    let status = status.map_err(|error| format!("Cannot run command: {error}"))?;
+    Ok(match status.code() {
+        Some(0) => ExitCode::SUCCESS,
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::strip_ansi;
+    use crate::util::LockedWriter;
+    use indoc::formatdoc;
+
+    fn run_all(path: &Path, commands: &[&[&str]]) -> String {
+        let io = LockedWriter::new(Vec::new());
+        for args in commands {
+            let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+            run_with(&args, path, io.clone()).unwrap();
+        }
+        strip_ansi(String::from_utf8_lossy(&io.unwrap()))
+    }
+
+    #[test]
+    fn matches_print_output() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("state");
+
+        let actual = run_all(
+            &path,
+            &[
+                &["h2", "Example", "Buildpack"],
+                &["warning", "Careful"],
+                &["bullet", "Ruby version"],
+                &["sub-bullet", "Installing"],
+                &["important", "Hello"],
+                &["sub-bullet", "Linking"],
+                &["bullet", "Gems"],
+                &["stream", "--", "echo", "hello world"],
+                &["timer", "Sleeping", "--", "true"],
+                &["done"],
+            ],
+        );
+
+        let mut stream = Print::new(Vec::new())
+            .h2("Example Buildpack")
+            .warning("Careful")
+            .bullet("Ruby version")
+            .sub_bullet("Installing")
+            .important("Hello")
+            .sub_bullet("Linking")
+            .done()
+            .bullet("Gems")
+            .start_stream(format!("Running {}", style::command("echo 'hello world'")));
+        writeln!(stream, "hello world").unwrap();
+        let expected = stream.done().start_timer("Sleeping").done().done().done();
+
+        assert_eq!(strip_ansi(String::from_utf8_lossy(&expected)), actual);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn error_ends_the_build() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("state");
+
+        let actual = run_all(
+            &path,
+            &[&["h1", "Example"], &["bullet", "Ruby"], &["error", "Oops"]],
+        );
+
+        let expected = formatdoc! {"
+
+            # Example
+
+            - Ruby
+
+            ! Oops
+
+        "};
+        assert_eq!(expected, actual);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn invalid_transitions() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("state");
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        let error = run_with(&args(&["sub-bullet", "Nope"]), &path, Vec::new()).unwrap_err();
+        assert_eq!(
+            "Command `sub-bullet` is not allowed before a header (h1 or h2)",
+            error
+        );
+
+        run_with(&args(&["h2", "Example"]), &path, Vec::new()).unwrap();
+        let error = run_with(&args(&["stream", "--", "true"]), &path, Vec::new()).unwrap_err();
+        assert_eq!("Command `stream` is not allowed outside of a bullet", error);
+
+        let error = run_with(&args(&["nope"]), &path, Vec::new()).unwrap_err();
+        assert!(error.starts_with("Unknown command `nope`"));
+    }
+
+    #[test]
+    fn command_error_saves_state() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("state");
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        run_with(&args(&["h2", "Example"]), &path, Vec::new()).unwrap();
+        run_with(&args(&["bullet", "Ruby"]), &path, Vec::new()).unwrap();
+        run_with(&args(&["warning", "Careful"]), &path, Vec::new()).unwrap();
+        assert_eq!(2, SavedState::read(&path).unwrap().newlines_since_last_char);
+
+        let error = run_with(
+            &args(&["timer", "--", "bullet_stream_missing_command"]),
+            &path,
+            Vec::new(),
+        )
+        .unwrap_err();
+        assert!(error.starts_with("Cannot run command"), "{error}");
+
+        // The timer line and its `Done` were written
+        assert_eq!(1, SavedState::read(&path).unwrap().newlines_since_last_char);
+    }
+
+    #[test]
+    fn test_is_help() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(is_help(&args(&[])));
+        assert!(is_help(&args(&["-h"])));
+        assert!(is_help(&args(&["--help", "bullet"])));
+        assert!(!is_help(&args(&["stream", "--", "ls", "-h"])));
+        assert!(!is_help(&args(&["sub-bullet", "--help"])));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!("echo", shell_quote("echo"));
+        assert_eq!("'hello world'", shell_quote("hello world"));
+        assert_eq!("'it'\\''s'", shell_quote("it's"));
+        assert_eq!("''", shell_quote(""));
+    }
+
+    #[test]
+    fn saved_state_round_trip() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("state");
+
+        assert_eq!(SavedState::default(), SavedState::read(&path).unwrap());
+
+        let saved = SavedState {
+            position: Position::SubBullet,
+            newlines_since_last_char: 2,
+            started_ms: Some(1_000),
+        };
+        saved.write(&path).unwrap();
+        assert_eq!(saved, SavedState::read(&path).unwrap());
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The source of time for every duration that [`crate::Print`] reports, and for the dots
+/// printed by a timer.
+///
+/// [`crate::Print`] uses the [`SystemClock`] unless another clock is provided via
+/// [`crate::Print::clock`]. Use a [`ManualClock`] to control time in tests and simulations:
+///
+/// ```rust
+/// use bullet_stream::{strip_ansi, ManualClock, Print};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let timer = Print::new(Vec::new())
+///     .clock(clock.clone())
+///     .without_header()
+///     .bullet("Ruby version")
+///     .start_timer("Installing");
+///
+/// clock.advance(Duration::from_secs(3));
+///
+/// let output = timer.done().done().done();
+/// assert_eq!(
+///     "- Ruby version\n  - Installing ...... (3.0s)\n- Done (finished in 3.0s)\n",
+///     strip_ansi(String::from_utf8_lossy(&output))
+/// );
+/// ```
+pub trait Clock: Debug + Clone + Send + Sync + 'static {
+    /// The current time.
+    ///
+    /// Must never go backwards.
+    fn now(&self) -> Instant;
+
+    /// The time elapsed since an earlier point in time read from this clock.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// Reads the time from [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only advances when told to.
+///
+/// Clones share the same time, so a clone can be given to [`crate::Print`] while the
+/// original is used to advance it.
+///
+/// Timer dots are derived from the clock: a timer prints one dot when it starts and one for
+/// every second the clock advances while it runs. Dots may be printed a moment after the clock
+/// is advanced, but by the time the timer is stopped the number of dots is exact.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl ManualClock {
+    /// A clock that starts at the current time.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self
+            .now
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *now += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self
+            .now
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manual_clock_only_advances_when_told() {
+        let clock = ManualClock::new();
+        let started = clock.now();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(Duration::ZERO, clock.elapsed(started));
+
+        clock.clone().advance(Duration::from_secs(2));
+        assert_eq!(Duration::from_secs(2), clock.elapsed(started));
+    }
+}
@@ -18,6 +18,24 @@ pub(crate) fn human(duration: &Duration) -> String {
     }
 }
 
+/// Whole seconds without the parts that are zero, for example `1m` or `1h 30s`.
+pub(crate) fn compact(duration: &Duration) -> String {
+    let hours = duration.as_secs() / 3600;
+    let minutes = (duration.as_secs() / 60) % 60;
+    let seconds = duration.as_secs() % 60;
+
+    let parts = [(hours, "h"), (minutes, "m"), (seconds, "s")]
+        .into_iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        String::from("0s")
+    } else {
+        parts.join(" ")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -63,4 +81,14 @@ mod test {
         let duration = Duration::from_millis(75 * 60 * 1000 - 1);
         assert_eq!(human(&duration), "1h 14m 59s");
     }
+
+    #[test]
+    fn test_compact_duration() {
+        assert_eq!(compact(&Duration::ZERO), "0s");
+        assert_eq!(compact(&Duration::from_millis(1999)), "1s");
+        assert_eq!(compact(&Duration::from_secs(60)), "1m");
+        assert_eq!(compact(&Duration::from_secs(90)), "1m 30s");
+        assert_eq!(compact(&Duration::from_secs(3630)), "1h 30s");
+        assert_eq!(compact(&Duration::from_secs(26 * 3600)), "26h");
+    }
 }
@@ -0,0 +1,306 @@
+//! A builder for paragraphs with the same shape across buildpacks.
+use crate::style;
+
+/// A structured message for [`crate::Print::error`], [`crate::Print::warning`] and
+/// [`crate::Print::important`].
+///
+/// The style guide asks for a title, an explanation and a way to fix the problem. Instead of
+/// formatting those by hand, build the message and pass it to any of the paragraph methods, it
+/// renders the sections in a fixed order and skips the ones that are not set:
+///
+/// ```rust
+/// use bullet_stream::{strip_ansi, ErrorMessage, Print};
+///
+/// let error = std::io::Error::new(std::io::ErrorKind::NotFound, "No such file or directory");
+/// let message = ErrorMessage::new("Could not read `Gemfile.lock`")
+///     .description("The file is required to install your dependencies.")
+///     .cause(&error)
+///     .debug_info("Path: /workspace/Gemfile.lock")
+///     .fix("Run `bundle install` locally and commit the `Gemfile.lock`.")
+///     .docs_url("https://bundler.io/man/gemfile.5.html");
+///
+/// let mut output = Vec::new();
+/// Print::new(&mut output)
+///     .without_header()
+///     .error(message);
+///
+/// assert_eq!(
+///     indoc::indoc! {"
+///
+///         ! Could not read `Gemfile.lock`
+///         !
+///         ! The file is required to install your dependencies.
+///         !
+///         ! Caused by:
+///         !   - No such file or directory
+///         !
+///         ! Debug information:
+///         ! Path: /workspace/Gemfile.lock
+///         !
+///         ! Suggested fix:
+///         ! Run `bundle install` locally and commit the `Gemfile.lock`.
+///         !
+///         ! For more information, see https://bundler.io/man/gemfile.5.html
+///
+///     "},
+///     strip_ansi(String::from_utf8_lossy(&output))
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ErrorMessage {
+    title: String,
+    description: Option<String>,
+    causes: Vec<String>,
+    debug_info: Vec<String>,
+    fix: Option<String>,
+    docs_url: Option<String>,
+    /// Kept up to date by each builder method so the message can be passed as `AsRef<str>`
+    rendered: String,
+}
+
+impl ErrorMessage {
+    /// A message with a one line summary of the problem.
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: None,
+            causes: Vec::new(),
+            debug_info: Vec::new(),
+            fix: None,
+            docs_url: None,
+            rendered: String::new(),
+        }
+        .render()
+    }
+
+    /// A message with the error as the title and its sources, see
+    /// [`std::error::Error::source`], listed under `Caused by:`.
+    #[must_use]
+    pub fn from_error(error: &dyn std::error::Error) -> Self {
+        let mut message = Self::new(error.to_string());
+        if let Some(source) = error.source() {
+            message = message.cause(source);
+        }
+        message
+    }
+
+    /// Explain what went wrong and why the build cannot continue.
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self.render()
+    }
+
+    /// List the error and each of its sources, see [`std::error::Error::source`], under
+    /// `Caused by:`.
+    ///
+    /// Can be called more than once, the causes are listed in order.
+    #[must_use]
+    pub fn cause(mut self, error: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            self.causes.push(error.to_string());
+            source = error.source();
+        }
+        self.render()
+    }
+
+    /// Add details that help to debug the problem, such as a path, a command or the contents of
+    /// a directory.
+    ///
+    /// Can be called more than once, each call adds a line under `Debug information:`.
+    #[must_use]
+    pub fn debug_info(mut self, info: impl Into<String>) -> Self {
+        self.debug_info.push(info.into());
+        self.render()
+    }
+
+    /// Tell the user how to fix the problem.
+    ///
+    /// Only include a fix when you are confident it applies, avoid generic advice such as "try
+    /// again later".
+    #[must_use]
+    pub fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self.render()
+    }
+
+    /// Link to documentation about the problem, it's printed last.
+    #[must_use]
+    pub fn docs_url(mut self, url: impl Into<String>) -> Self {
+        self.docs_url = Some(url.into());
+        self.render()
+    }
+
+    fn render(mut self) -> Self {
+        let mut sections = vec![self.title.trim().to_string()];
+        if let Some(description) = &self.description {
+            sections.push(description.trim().to_string());
+        }
+        if !self.causes.is_empty() {
+            let causes = self
+                .causes
+                .iter()
+                .map(|cause| format!("  - {}", cause.trim()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            sections.push(format!("Caused by:\n{causes}"));
+        }
+        if !self.debug_info.is_empty() {
+            let debug_info = self
+                .debug_info
+                .iter()
+                .map(|info| info.trim())
+                .collect::<Vec<_>>()
+                .join("\n");
+            sections.push(format!("Debug information:\n{debug_info}"));
+        }
+        if let Some(fix) = &self.fix {
+            sections.push(format!("Suggested fix:\n{}", fix.trim()));
+        }
+        if let Some(url) = &self.docs_url {
+            sections.push(format!(
+                "For more information, see {}",
+                style::url(url.trim())
+            ));
+        }
+        self.rendered = sections.join("\n\n");
+        self
+    }
+}
+
+impl AsRef<str> for ErrorMessage {
+    fn as_ref(&self) -> &str {
+        &self.rendered
+    }
+}
+
+impl std::fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::strip_ansi;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+    use std::fmt::{Display, Formatter};
+
+    #[derive(Debug)]
+    struct Outer(std::io::Error);
+
+    impl Display for Outer {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Download failed")
+        }
+    }
+
+    impl std::error::Error for Outer {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn title_only() {
+        assert_eq!(
+            "Missing `Gemfile`",
+            ErrorMessage::new("Missing `Gemfile`\n").as_ref()
+        );
+    }
+
+    #[test]
+    fn cause_chain() {
+        let error = Outer(std::io::Error::other("connection reset"));
+        let message = ErrorMessage::new("Could not install Ruby")
+            .cause(&error)
+            .docs_url("https://example.com");
+        assert_eq!(
+            formatdoc! {"
+                Could not install Ruby
+
+                Caused by:
+                  - Download failed
+                  - connection reset
+
+                For more information, see https://example.com"},
+            strip_ansi(&message)
+        );
+    }
+
+    #[test]
+    fn from_error() {
+        let error = Outer(std::io::Error::other("connection reset"));
+        assert_eq!(
+            formatdoc! {"
+                Download failed
+
+                Caused by:
+                  - connection reset"},
+            ErrorMessage::from_error(&error).as_ref()
+        );
+        assert_eq!(
+            "connection reset",
+            ErrorMessage::from_error(&error.0).as_ref()
+        );
+    }
+
+    #[test]
+    fn error_from_lists_sources() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        print(&capture, &clock)
+            .h2("Example Buildpack")
+            .bullet("Ruby version")
+            .done()
+            .error_from(&Outer(std::io::Error::other("connection reset")));
+
+        assert_eq!(
+            formatdoc! {"
+
+                ## Example Buildpack
+
+                - Ruby version
+
+                ! Download failed
+                !
+                ! Caused by:
+                !   - connection reset
+
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn warning_with_message() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        print(&capture, &clock)
+            .without_header()
+            .warning(ErrorMessage::new("Deprecated Ruby version").fix("Upgrade to Ruby 3.3"))
+            .bullet("Ruby version")
+            .done()
+            .done();
+
+        assert_eq!(
+            formatdoc! {"
+
+                ! Deprecated Ruby version
+                !
+                ! Suggested fix:
+                ! Upgrade to Ruby 3.3
+
+                - Ruby version
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+    }
+}
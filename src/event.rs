@@ -0,0 +1,180 @@
+//! Machine readable output, one JSON object per line (JSON Lines).
+//!
+//! Every transition in [`crate::Print`] can optionally be mirrored to a second writer
+//! so that the output can be indexed without having to scrape the human readable text.
+use crate::ansi_escape::strip_ansi;
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The type of transition an event describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EventKind {
+    H1,
+    H2,
+    Bullet,
+    SubBullet,
+    StreamStart,
+    StreamDone,
+    TimerStart,
+    TimerDone,
+    TimerCancel,
+    TasksStart,
+    TasksDone,
+    ProgressStart,
+    ProgressDone,
+    ProgressCancel,
+    Warning,
+    WarningSuppressed,
+    Important,
+    Error,
+    SectionDone,
+    Done,
+}
+
+impl EventKind {
+    fn to_str(self) -> &'static str {
+        match self {
+            EventKind::H1 => "h1",
+            EventKind::H2 => "h2",
+            EventKind::Bullet => "bullet",
+            EventKind::SubBullet => "sub_bullet",
+            EventKind::StreamStart => "stream_start",
+            EventKind::StreamDone => "stream_done",
+            EventKind::TimerStart => "timer_start",
+            EventKind::TimerDone => "timer_done",
+            EventKind::TimerCancel => "timer_cancel",
+            EventKind::TasksStart => "tasks_start",
+            EventKind::TasksDone => "tasks_done",
+            EventKind::ProgressStart => "progress_start",
+            EventKind::ProgressDone => "progress_done",
+            EventKind::ProgressCancel => "progress_cancel",
+            EventKind::Warning => "warning",
+            EventKind::WarningSuppressed => "warning_suppressed",
+            EventKind::Important => "important",
+            EventKind::Error => "error",
+            EventKind::SectionDone => "section_done",
+            EventKind::Done => "done",
+        }
+    }
+}
+
+/// Writes one JSON object per event to the wrapped writer.
+///
+/// The writer is shared, so cloning an `EventLog` produces a second handle to the same
+/// destination. Each event is written with a single `write_all` call while the lock is
+/// held so events are never interleaved.
+#[derive(Clone)]
+pub(crate) struct EventLog {
+    io: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Debug for EventLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventLog")
+            .field("io", &"dyn Write")
+            .finish()
+    }
+}
+
+impl EventLog {
+    pub(crate) fn new(io: impl Write + Send + 'static) -> Self {
+        Self {
+            io: Arc::new(Mutex::new(Box::new(io))),
+        }
+    }
+
+    /// Emit a single event.
+    ///
+    /// The event stream is a secondary output. A failure to write to it is ignored so that it
+    /// never interrupts the primary (human readable) output.
+    pub(crate) fn emit(
+        &self,
+        kind: EventKind,
+        text: impl AsRef<str>,
+        depth: usize,
+        duration: Option<Duration>,
+    ) {
+        let line = format_event(kind, text.as_ref(), depth, SystemTime::now(), duration);
+        if let Ok(mut io) = self.io.lock() {
+            let _ = io.write_all(line.as_bytes()).and_then(|()| io.flush());
+        }
+    }
+}
+
+fn format_event(
+    kind: EventKind,
+    text: &str,
+    depth: usize,
+    now: SystemTime,
+    duration: Option<Duration>,
+) -> String {
+    let timestamp = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis();
+    let mut line = format!(
+        r#"{{"kind":{},"text":{},"depth":{depth},"timestamp_ms":{timestamp}"#,
+        json_string(kind.to_str()),
+        json_string(strip_ansi(text).trim()),
+    );
+    if let Some(duration) = duration {
+        line.push_str(&format!(r#","duration_ms":{}"#, duration.as_millis()));
+    }
+    line.push_str("}\n");
+    line
+}
+
+/// Quotes and escapes a string per the JSON spec.
+fn json_string(contents: &str) -> String {
+    let mut result = String::with_capacity(contents.len() + 2);
+    result.push('"');
+    for char in contents.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(r#""hello""#, json_string("hello"));
+        assert_eq!(r#""say \"hi\"""#, json_string(r#"say "hi""#));
+        assert_eq!(r#""a\\b""#, json_string(r"a\b"));
+        assert_eq!(r#""one\ntwo\tthree""#, json_string("one\ntwo\tthree"));
+        assert_eq!(r#""\u001b""#, json_string("\x1B"));
+    }
+
+    #[test]
+    fn test_format_event() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_500);
+        assert_eq!(
+            "{\"kind\":\"bullet\",\"text\":\"Ruby version\",\"depth\":1,\"timestamp_ms\":1500}\n",
+            format_event(EventKind::Bullet, "Ruby version\n", 1, now, None)
+        );
+
+        assert_eq!(
+            "{\"kind\":\"timer_done\",\"text\":\"Installing\",\"depth\":2,\"timestamp_ms\":1500,\"duration_ms\":42}\n",
+            format_event(
+                EventKind::TimerDone,
+                "\x1B[0;33mInstalling\x1B[0m",
+                2,
+                now,
+                Some(Duration::from_millis(42))
+            )
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Printing a line when a stream has been quiet for a while, so it's clear the command did not
+//! hang. See [`crate::StreamOptions::heartbeat`].
+use crate::Clock;
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Decides when a heartbeat line is due.
+pub(crate) struct Heartbeat {
+    quiet_period: Duration,
+    now: Box<dyn Fn() -> Instant + Send>,
+    started: Instant,
+    last_output: Instant,
+    /// Formats the line, including the trailing newline, for the time since the stream started.
+    line: Box<dyn Fn(Duration) -> String + Send>,
+}
+
+impl Debug for Heartbeat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heartbeat")
+            .field("quiet_period", &self.quiet_period)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Heartbeat {
+    pub(crate) fn new<C: Clock>(
+        clock: C,
+        quiet_period: Duration,
+        line: impl Fn(Duration) -> String + Send + 'static,
+    ) -> Self {
+        let started = clock.now();
+        Self {
+            quiet_period: quiet_period.max(Duration::from_millis(1)),
+            now: Box::new(move || clock.now()),
+            started,
+            last_output: started,
+            line: Box::new(line),
+        }
+    }
+
+    /// Restart the quiet period.
+    pub(crate) fn reset(&mut self) {
+        self.last_output = (self.now)();
+    }
+
+    /// Returns the line to print if the quiet period passed, and the time until the next check.
+    pub(crate) fn poll(&mut self) -> (Option<String>, Duration) {
+        let now = (self.now)();
+        let quiet = now.saturating_duration_since(self.last_output);
+        if quiet >= self.quiet_period {
+            self.last_output = now;
+            let line = (self.line)(now.saturating_duration_since(self.started));
+            (Some(line), self.quiet_period)
+        } else {
+            (None, self.quiet_period - quiet)
+        }
+    }
+}
+
+/// Receive the next message, printing heartbeats via `beat` while waiting.
+///
+/// Returns `None` once all senders are dropped.
+pub(crate) fn recv<T>(
+    receiver: &Receiver<T>,
+    heartbeat: Option<&mut Heartbeat>,
+    mut beat: impl FnMut(String),
+) -> Option<T> {
+    let Some(heartbeat) = heartbeat else {
+        return receiver.recv().ok();
+    };
+
+    loop {
+        let (line, wait) = heartbeat.poll();
+        if let Some(line) = line {
+            beat(line);
+        }
+        match receiver.recv_timeout(wait) {
+            Ok(message) => {
+                heartbeat.reset();
+                return Some(message);
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+/// Writes to the inner writer, and prints a heartbeat from a background thread when nothing
+/// was written for a while.
+///
+/// The heartbeat is only written between writes, so when the inner writer receives whole lines
+/// the heartbeat never ends up in the middle of a line.
+pub(crate) struct HeartbeatWrite<W> {
+    shared: Arc<Mutex<Option<Shared<W>>>>,
+    /// Boxed to keep the stream state small.
+    thread: Option<Box<(Sender<()>, JoinHandle<()>)>>,
+}
+
+struct Shared<W> {
+    inner: W,
+    heartbeat: Option<Heartbeat>,
+    /// The result of the last heartbeat, returned by the next write.
+    result: std::io::Result<()>,
+}
+
+impl<W> Debug for HeartbeatWrite<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeartbeatWrite")
+            .field("running", &self.thread.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write + Send + 'static> HeartbeatWrite<W> {
+    /// Without a heartbeat no background thread is started and writes are passed through.
+    pub(crate) fn new(inner: W, heartbeat: Option<Heartbeat>) -> Self {
+        let has_heartbeat = heartbeat.is_some();
+        let shared = Arc::new(Mutex::new(Some(Shared {
+            inner,
+            heartbeat,
+            result: Ok(()),
+        })));
+        if !has_heartbeat {
+            return Self {
+                shared,
+                thread: None,
+            };
+        }
+
+        let (sender, receiver) = channel::<()>();
+        let join_handle = std::thread::spawn({
+            let shared = Arc::clone(&shared);
+            move || loop {
+                let wait = match lock(&shared).as_mut() {
+                    Some(Shared {
+                        inner,
+                        heartbeat: Some(heartbeat),
+                        result,
+                    }) => {
+                        let (line, wait) = heartbeat.poll();
+                        if let (Some(line), Ok(())) = (line, &result) {
+                            *result = inner
+                                .write_all(line.as_bytes())
+                                .and_then(|()| inner.flush());
+                        }
+                        wait
+                    }
+                    _ => return,
+                };
+                match receiver.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        });
+
+        Self {
+            shared,
+            thread: Some(Box::new((sender, join_handle))),
+        }
+    }
+}
+
+impl<W> HeartbeatWrite<W> {
+    /// Stop the heartbeat and return the inner writer.
+    #[allow(clippy::missing_panics_doc)]
+    pub(crate) fn into_inner(mut self) -> W {
+        self.stop();
+        match lock(&self.shared).take() {
+            Some(shared) => shared.inner,
+            // Only `into_inner` takes the value, and it consumes `self`
+            None => panic!("Internal error: heartbeat writer was already unwrapped"),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let (stop_signal, join_handle) = *thread;
+            // Ignore if the channel is closed, it means the thread already stopped
+            let _ = stop_signal.send(());
+            let _ = join_handle.join();
+        }
+    }
+}
+
+impl<W> Drop for HeartbeatWrite<W> {
+    fn drop(&mut self) {
+        // Ensure nothing is written after the stream is dropped
+        self.stop();
+    }
+}
+
+impl<W: Write> Write for HeartbeatWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut shared = lock(&self.shared);
+        let Some(shared) = shared.as_mut() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        };
+        std::mem::replace(&mut shared.result, Ok(()))?;
+        if let Some(heartbeat) = shared.heartbeat.as_mut() {
+            heartbeat.reset();
+        }
+        shared.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match lock(&self.shared).as_mut() {
+            Some(shared) => shared.inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ManualClock;
+
+    fn line(elapsed: Duration) -> String {
+        format!("({}s)\n", elapsed.as_secs())
+    }
+
+    #[test]
+    fn test_poll() {
+        let clock = ManualClock::new();
+        let mut heartbeat = Heartbeat::new(clock.clone(), Duration::from_secs(10), line);
+        assert_eq!((None, Duration::from_secs(10)), heartbeat.poll());
+
+        clock.advance(Duration::from_secs(4));
+        heartbeat.reset();
+        clock.advance(Duration::from_secs(9));
+        assert_eq!((None, Duration::from_secs(1)), heartbeat.poll());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            (Some(String::from("(14s)\n")), Duration::from_secs(10)),
+            heartbeat.poll()
+        );
+        assert_eq!((None, Duration::from_secs(10)), heartbeat.poll());
+    }
+
+    #[test]
+    fn test_recv() {
+        let clock = ManualClock::new();
+        let mut heartbeat = Heartbeat::new(clock.clone(), Duration::from_secs(10), line);
+        let (sender, receiver) = channel();
+        let mut beats = Vec::new();
+
+        // Messages are queued before each call, so `recv` never waits
+        sender.send("compiling").unwrap();
+        clock.advance(Duration::from_secs(10));
+        let message = recv(&receiver, Some(&mut heartbeat), |line| beats.push(line));
+        assert_eq!(Some("compiling"), message);
+        assert_eq!(vec![String::from("(10s)\n")], beats);
+
+        sender.send("linking").unwrap();
+        clock.advance(Duration::from_secs(9));
+        let message = recv(&receiver, Some(&mut heartbeat), |line| beats.push(line));
+        assert_eq!(Some("linking"), message);
+        assert_eq!(1, beats.len());
+
+        drop(sender);
+        clock.advance(Duration::from_secs(10));
+        let message = recv(&receiver, Some(&mut heartbeat), |line| beats.push(line));
+        assert_eq!(None, message);
+        assert_eq!(
+            vec![String::from("(10s)\n"), String::from("(29s)\n")],
+            beats
+        );
+    }
+
+    #[test]
+    fn without_heartbeat_recv_does_not_beat() {
+        let (sender, receiver) = channel();
+        sender.send("compiling").unwrap();
+        drop(sender);
+
+        let mut beats = Vec::new();
+        assert_eq!(
+            Some("compiling"),
+            recv(&receiver, None, |line| beats.push(line))
+        );
+        assert_eq!(None, recv(&receiver, None, |line| beats.push(line)));
+        assert!(beats.is_empty());
+    }
+
+    #[test]
+    fn without_heartbeat_writes_pass_through() {
+        let mut write = HeartbeatWrite::new(Vec::new(), None);
+        write.write_all(b"hello\n").unwrap();
+        assert_eq!(b"hello\n".to_vec(), write.into_inner());
+    }
+}
@@ -1,22 +1,67 @@
 #![doc = include_str!("../README.md")]
 
 use crate::ansi_escape::ANSI;
+use crate::event::{EventKind, EventLog};
+use crate::heartbeat::{Heartbeat, HeartbeatWrite};
+use crate::line_filter::LineFilter;
+use crate::panic_guard::PanicGuard;
+use crate::redact::Secrets;
+use crate::summary::SectionTimes;
 use crate::util::{
-    mpsc_stream_to_output, prefix_first_rest_lines, prefix_lines, ParagraphInspectWrite,
+    mpsc_stream_to_output, prefix_first_rest_lines, prefix_lines, ParagraphInspectWrite, Source,
 };
+use crate::warnings::Warnings;
 use crate::write::line_mapped;
 use std::fmt::Debug;
 use std::io::Write;
-use std::time::Instant;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 mod ansi_escape;
+#[cfg(feature = "tokio")]
+mod async_stream;
 mod background_printer;
+mod byte_format;
+mod capture;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli;
+mod clock;
 mod duration_format;
+mod error_message;
+mod event;
+mod heartbeat;
+mod line_filter;
+mod panic_guard;
+mod parallel;
+mod print_error;
+mod progress;
+mod redact;
+mod stream_options;
 pub mod style;
+mod summary;
+mod tasks;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod timer_options;
 mod util;
+mod warnings;
 mod write;
 
-pub use ansi_escape::strip_ansi;
+pub use ansi_escape::{strip_ansi, ColorChoice};
+#[cfg(feature = "tokio")]
+pub use async_stream::AsyncStreamWriter;
+pub use capture::CapturedOutput;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use error_message::ErrorMessage;
+pub use parallel::{FlushOrder, ParallelWrite};
+pub use print_error::{PrintError, PrintResult};
+pub use progress::{ProgressRead, ProgressWrite};
+pub use stream_options::StreamOptions;
+pub use tasks::TaskTracker;
+pub use timer_options::TimerOptions;
+pub use warnings::WarningCounts;
 
 /// Use [`Print`] to output structured text as a buildpack/script executes. The output
 /// is intended to be read by the application user.
@@ -36,11 +81,75 @@ pub use ansi_escape::strip_ansi;
 /// ```
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
-pub struct Print<T> {
+pub struct Print<T, C = SystemClock> {
     pub(crate) started: Option<Instant>,
+    pub(crate) events: Option<EventLog>,
+    pub(crate) secrets: Secrets,
+    pub(crate) warnings: Warnings,
+    pub(crate) sections: SectionTimes,
+    pub(crate) clock: C,
     pub(crate) state: T,
 }
 
+impl<T, C> Print<T, C> {
+    /// Register a secret value, such as an API token, that must never be written to the output.
+    ///
+    /// Every occurrence of the secret is replaced with `[REDACTED]` in headers, bullets,
+    /// paragraphs, streamed output and the event stream. The secret is carried through every
+    /// state transition, including to streams and timers that are already running. Streamed
+    /// output is redacted one line at a time, so a secret split across multiple writes is
+    /// still redacted as long as it does not contain a newline.
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    /// use std::io::Write;
+    ///
+    /// let token = String::from("hunter2");
+    /// let mut stream = Print::new(Vec::new())
+    ///     .redact(&token)
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Deploying")
+    ///     .start_stream("Uploading");
+    ///
+    /// write!(&mut stream, "Using token hun").unwrap();
+    /// writeln!(&mut stream, "ter2").unwrap();
+    ///
+    /// let output = stream.done().done().done();
+    /// assert!(!String::from_utf8_lossy(&output).contains(&token));
+    /// ```
+    #[must_use]
+    pub fn redact(self, secret: impl Into<String>) -> Self {
+        self.secrets.add(secret);
+        self
+    }
+
+    /// Transition to a new state, carrying over everything that isn't state specific.
+    fn map_state<S>(self, f: impl FnOnce(T) -> S) -> Print<S, C> {
+        Print {
+            started: self.started,
+            events: self.events,
+            secrets: self.secrets,
+            warnings: self.warnings,
+            sections: self.sections,
+            clock: self.clock,
+            state: f(self.state),
+        }
+    }
+
+    /// Mirror a transition to the event stream, if one is configured.
+    fn emit(
+        &self,
+        kind: EventKind,
+        text: impl AsRef<str>,
+        depth: usize,
+        duration: Option<Duration>,
+    ) {
+        if let Some(events) = &self.events {
+            events.emit(kind, self.secrets.redact(text), depth, duration);
+        }
+    }
+}
+
 #[deprecated(
     since = "0.2.0",
     note = "bullet_stream::Output conflicts with std::io::Output, prefer Print"
@@ -53,8 +162,14 @@ pub type Output<T> = Print<T>;
 /// represent the various states. See struct documentation for more details.
 pub mod state {
     use crate::background_printer::PrintGuard;
+    use crate::heartbeat::HeartbeatWrite;
+    use crate::line_filter::LineFilter;
+    use crate::progress::ByteCount;
     use crate::util::ParagraphInspectWrite;
     use crate::write::MappedWrite;
+    use crate::TaskTracker;
+    use std::marker::PhantomData;
+    use std::sync::Arc;
     use std::time::Instant;
 
     /// At the start of a stream you can output a header (h1) or subheader (h2).
@@ -151,9 +266,47 @@ pub mod state {
     ///     output.done()
     ///}
     /// ```
+    ///
+    /// A section can contain child sections that are indented one level deeper. Each child
+    /// section is finished back to its parent via `done()`. The `P` type parameter records the
+    /// state to return to and defaults to [`Bullet`].
+    ///
+    /// ```rust
+    /// use bullet_stream::{Print, state::{Bullet, SubBullet}};
+    /// use std::io::Write;
+    ///
+    /// let output = Print::new(Vec::new())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Workspaces");
+    ///
+    /// let io = install_workspace(output, "api").done().done();
+    ///
+    /// use indoc::formatdoc;
+    /// use bullet_stream::strip_ansi;
+    /// assert_eq!(
+    ///     formatdoc!
+    ///         {"## Example Buildpack
+    ///
+    ///           - Workspaces
+    ///             - Workspace `api`
+    ///               - Installing
+    ///           - Done (finished in < 0.1s)
+    ///         "}.trim(),
+    ///     strip_ansi(String::from_utf8_lossy(&io)).trim()
+    /// );
+    ///
+    /// fn install_workspace<W>(output: Print<SubBullet<W>>, name: &str) -> Print<SubBullet<W>>
+    /// where W: Write + Send + Sync + 'static {
+    ///     let workspace: Print<SubBullet<W, SubBullet<W>>> = output
+    ///         .section(format!("Workspace `{name}`"));
+    ///
+    ///     workspace.sub_bullet("Installing").done()
+    ///}
+    /// ```
     #[derive(Debug)]
-    pub struct SubBullet<W> {
+    pub struct SubBullet<W, P = Bullet<W>> {
         pub(crate) write: ParagraphInspectWrite<W>,
+        pub(crate) parent: PhantomData<fn() -> P>,
     }
 
     /// This state is intended for streaming output from a process to the end user. It is
@@ -183,9 +336,11 @@ pub mod state {
     ///}
     /// ```
     #[derive(Debug)]
-    pub struct Stream<W: std::io::Write> {
+    pub struct Stream<W: std::io::Write, P = Bullet<W>> {
         pub(crate) started: Instant,
-        pub(crate) write: MappedWrite<ParagraphInspectWrite<W>>,
+        pub(crate) write: MappedWrite<HeartbeatWrite<ParagraphInspectWrite<W>>>,
+        pub(crate) filter: Arc<LineFilter>,
+        pub(crate) parent: PhantomData<fn() -> P>,
     }
 
     /// This state is intended for long-running tasks that do not stream but wish to convey progress
@@ -214,9 +369,167 @@ pub mod state {
     ///}
     /// ```
     #[derive(Debug)]
-    pub struct Background<W: std::io::Write> {
+    pub struct Background<W: std::io::Write, P = Bullet<W>> {
+        pub(crate) started: Instant,
+        pub(crate) write: PrintGuard<ParagraphInspectWrite<W>>,
+        pub(crate) parent: PhantomData<fn() -> P>,
+    }
+
+    /// This state reports the progress of a download, or any other transfer of bytes. Instead of
+    /// dots, a marker is printed for each milestone that is passed, such as `10%...`.
+    ///
+    /// This state is started from a [`SubBullet`] and finished back to a [`SubBullet`].
+    ///
+    /// ```rust
+    /// use bullet_stream::{Print, state::{Bullet, SubBullet}};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut output = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Ruby version");
+    ///
+    /// download_ruby(output, std::io::empty(), None).done();
+    ///
+    /// fn download_ruby<W>(
+    ///     mut output: Print<SubBullet<W>>,
+    ///     body: impl Read,
+    ///     size: Option<u64>,
+    /// ) -> Print<SubBullet<W>>
+    /// where W: Write + Send + Sync + 'static {
+    ///     let download = output.start_progress("Downloading", size);
+    ///
+    ///     std::io::copy(&mut download.reader(body), &mut std::io::sink()).unwrap();
+    ///
+    ///     download.done()
+    ///}
+    /// ```
+    #[derive(Debug)]
+    pub struct Progress<W: std::io::Write, P = Bullet<W>> {
+        pub(crate) started: Instant,
+        pub(crate) count: Arc<ByteCount>,
+        pub(crate) write: PrintGuard<ParagraphInspectWrite<W>>,
+        pub(crate) parent: PhantomData<fn() -> P>,
+    }
+
+    /// This state tracks several named tasks that run in parallel. Instead of dots, a summary of
+    /// the finished tasks is printed periodically. Tasks are marked as finished through a
+    /// [`TaskTracker`], usually from other threads.
+    ///
+    /// This state is started from a [`SubBullet`] and finished back to a [`SubBullet`].
+    ///
+    /// ```rust
+    /// use bullet_stream::{Print, state::{Bullet, SubBullet}};
+    /// use std::io::Write;
+    ///
+    /// let mut output = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Dependencies");
+    ///
+    /// install_dependencies(output).done();
+    ///
+    /// fn install_dependencies<W>(mut output: Print<SubBullet<W>>) -> Print<SubBullet<W>>
+    /// where W: Write + Send + Sync + 'static {
+    ///     let tasks = output.start_tasks("Installing", ["ruby", "node"]);
+    ///
+    ///     std::thread::scope(|scope| {
+    ///         for name in ["ruby", "node"] {
+    ///             let tracker = tasks.tracker();
+    ///             scope.spawn(move || {
+    ///                 // ...
+    ///                 tracker.finish(name);
+    ///             });
+    ///         }
+    ///     });
+    ///
+    ///     tasks.done()
+    ///}
+    /// ```
+    #[derive(Debug)]
+    pub struct Tasks<W: std::io::Write, P = Bullet<W>> {
         pub(crate) started: Instant,
+        pub(crate) tracker: TaskTracker,
         pub(crate) write: PrintGuard<ParagraphInspectWrite<W>>,
+        pub(crate) parent: PhantomData<fn() -> P>,
+    }
+
+    /// A state that a `state::SubBullet` section can be finished back to: a `state::Bullet`, or
+    /// a `state::SubBullet` at any depth.
+    ///
+    /// Use it as a bound to write helpers that work at any depth. It's implemented for all such
+    /// states and cannot be implemented outside of this crate.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use bullet_stream::{Print, state::{SectionParent, SubBullet}};
+    /// use std::io::Write;
+    ///
+    /// let output = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Ruby version");
+    /// let output = log_ruby_version(output).done();
+    ///
+    /// let nested = output.bullet("Bundler").section("Checking Ruby");
+    /// log_ruby_version(nested).done().done().done();
+    ///
+    /// fn log_ruby_version<W, P>(output: Print<SubBullet<W, P>>) -> Print<SubBullet<W, P>>
+    /// where
+    ///     W: Write + Send + Sync + 'static,
+    ///     P: SectionParent<W>,
+    /// {
+    ///     output.sub_bullet("Using Ruby 3.3.0")
+    /// }
+    /// ```
+    pub trait SectionParent<W: std::io::Write>: crate::private::Sealed<W> {}
+
+    impl<W, T> SectionParent<W> for T
+    where
+        W: std::io::Write,
+        T: crate::private::Sealed<W>,
+    {
+    }
+}
+
+use state::SectionParent;
+
+// `ParagraphInspectWrite` is crate private, the sealed trait is unnameable outside the crate
+#[allow(private_interfaces)]
+mod private {
+    use crate::util::ParagraphInspectWrite;
+    use std::io::Write;
+
+    /// The items of [`crate::state::SectionParent`], which also prevent it from being
+    /// implemented outside of this crate.
+    pub trait Sealed<W: Write> {
+        /// The depth of this state's own bullet point, top-level bullets have a depth of one.
+        const DEPTH: usize;
+
+        fn from_write(write: ParagraphInspectWrite<W>) -> Self;
+    }
+}
+
+#[allow(private_interfaces)]
+impl<W: Write> private::Sealed<W> for state::Bullet<W> {
+    const DEPTH: usize = 1;
+
+    fn from_write(write: ParagraphInspectWrite<W>) -> Self {
+        state::Bullet { write }
+    }
+}
+
+#[allow(private_interfaces)]
+impl<W, P> private::Sealed<W> for state::SubBullet<W, P>
+where
+    W: Write,
+    P: SectionParent<W>,
+{
+    const DEPTH: usize = P::DEPTH + 1;
+
+    fn from_write(write: ParagraphInspectWrite<W>) -> Self {
+        state::SubBullet {
+            write,
+            parent: PhantomData,
+        }
     }
 }
 
@@ -228,7 +541,7 @@ trait AnnounceSupportedState {
 }
 
 /// Used for announcements such as warning and error states
-impl<W> AnnounceSupportedState for state::SubBullet<W>
+impl<W, P> AnnounceSupportedState for state::SubBullet<W, P>
 where
     W: Write,
 {
@@ -253,9 +566,10 @@ where
 
 /// Used for announcements such as warning and error states
 #[allow(private_bounds)]
-impl<S> Print<S>
+impl<S, C> Print<S, C>
 where
     S: AnnounceSupportedState,
+    C: Clock,
 {
     /// Emit an error and end the build output.
     ///
@@ -272,11 +586,67 @@ where
     /// that in the error message. Do not write a generic suggestion like "try again later" unless
     /// you are certain that the error is transient.
     ///
+    /// To give every error the same shape, pass an [`ErrorMessage`] with these sections.
+    ///
     /// If you detect something problematic but not bad enough to halt buildpack execution, consider
     /// using a [`Print::warning`] instead.
     ///
-    pub fn error(mut self, s: impl AsRef<str>) {
-        self.write_paragraph(&ANSI::Red, s);
+    pub fn error(self, s: impl AsRef<str>) {
+        self.try_error(s).expect("Output error: UI writer closed");
+    }
+
+    /// Like [`Print::error`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_error(mut self, s: impl AsRef<str>) -> Result<(), PrintError<()>> {
+        self.emit(EventKind::Error, s.as_ref(), 0, None);
+        PrintError::from_io((), self.write_paragraph(&ANSI::Red, s))
+    }
+
+    /// Emit an error and end the build output, like [`Print::error`], with the message of the
+    /// given error and each of its sources.
+    ///
+    /// Formatting an error with `format!("{error}")` only shows the outermost message. This
+    /// lists the chain of [`std::error::Error::source`] errors under `Caused by:`:
+    ///
+    /// ```rust
+    /// use bullet_stream::{strip_ansi, Print};
+    ///
+    /// #[derive(Debug)]
+    /// struct DownloadError(std::io::Error);
+    ///
+    /// impl std::fmt::Display for DownloadError {
+    ///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    ///         write!(f, "Could not download Ruby")
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for DownloadError {
+    ///     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    ///         Some(&self.0)
+    ///     }
+    /// }
+    ///
+    /// let error = DownloadError(std::io::Error::other("connection reset"));
+    /// let mut output = Vec::new();
+    /// Print::new(&mut output)
+    ///     .without_header()
+    ///     .error_from(&error);
+    ///
+    /// assert_eq!(
+    ///     "\n! Could not download Ruby\n!\n! Caused by:\n!   - connection reset\n\n",
+    ///     strip_ansi(String::from_utf8_lossy(&output))
+    /// );
+    /// ```
+    ///
+    /// To add a fix or other sections, use [`ErrorMessage::from_error`] with [`Print::error`].
+    pub fn error_from(self, error: &dyn std::error::Error) {
+        self.error(ErrorMessage::from_error(error));
+    }
+
+    /// Like [`Print::error_from`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_error_from(self, error: &dyn std::error::Error) -> Result<(), PrintError<()>> {
+        self.try_error(ErrorMessage::from_error(error))
     }
 
     /// Emit a warning message to the end user.
@@ -296,10 +666,35 @@ where
     ///
     /// Warnings will be output in a multi-line paragraph style. A warning can be emitted from any
     /// state except for [`state::Header`].
+    ///
+    /// To let users hide a warning, give it an identifier with [`Print::warning_with_id`].
     #[must_use]
-    pub fn warning(mut self, s: impl AsRef<str>) -> Print<S> {
-        self.write_paragraph(&ANSI::Yellow, s);
-        self
+    pub fn warning(self, s: impl AsRef<str>) -> Print<S, C> {
+        self.try_warning(s).expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::warning`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_warning(mut self, s: impl AsRef<str>) -> PrintResult<S, C> {
+        self.emit(EventKind::Warning, s.as_ref(), 0, None);
+        let result = self.write_paragraph(&ANSI::Yellow, s.as_ref());
+        if result.is_ok() {
+            self.warnings.record_shown(s.as_ref());
+        }
+        PrintError::from_io(self, result)
+    }
+
+    /// Emit a warning, like [`Print::warning`], with the message of the given error and each
+    /// of its sources listed under `Caused by:`. See [`Print::error_from`].
+    #[must_use]
+    pub fn warning_from(self, error: &dyn std::error::Error) -> Print<S, C> {
+        self.warning(ErrorMessage::from_error(error))
+    }
+
+    /// Like [`Print::warning_from`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_warning_from(self, error: &dyn std::error::Error) -> PrintResult<S, C> {
+        self.try_warning(ErrorMessage::from_error(error))
     }
 
     /// Emit an important message to the end user.
@@ -313,36 +708,21 @@ where
     /// but not necessarily act on. If the message is actionable, consider using a
     /// [`Print::warning`] instead.
     #[must_use]
-    pub fn important(mut self, s: impl AsRef<str>) -> Print<S> {
-        self.write_paragraph(&ANSI::BoldCyan, s);
-        self
+    pub fn important(self, s: impl AsRef<str>) -> Print<S, C> {
+        self.try_important(s)
+            .expect("Output error: UI writer closed")
     }
 
-    fn write_paragraph(&mut self, color: &ANSI, s: impl AsRef<str>) {
-        let io = self.state.write_mut();
-        let contents = s.as_ref().trim();
-
-        if !io.was_paragraph {
-            writeln_now(io, "");
-        }
+    /// Like [`Print::important`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_important(mut self, s: impl AsRef<str>) -> PrintResult<S, C> {
+        self.emit(EventKind::Important, s.as_ref(), 0, None);
+        let result = self.write_paragraph(&ANSI::BoldCyan, s);
+        PrintError::from_io(self, result)
+    }
 
-        writeln_now(
-            io,
-            ansi_escape::wrap_ansi_escape_each_line(
-                color,
-                prefix_lines(contents, |_, line| {
-                    // Avoid adding trailing whitespace to the line, if there was none already.
-                    // The `\n` case is required since `prefix_lines` uses `str::split_inclusive`,
-                    // which preserves any trailing newline characters if present.
-                    if line.is_empty() || line == "\n" {
-                        String::from("!")
-                    } else {
-                        String::from("! ")
-                    }
-                }),
-            ),
-        );
-        writeln_now(io, "");
+    fn write_paragraph(&mut self, color: &ANSI, s: impl AsRef<str>) -> std::io::Result<()> {
+        write_paragraph(self.state.write_mut(), color, s)
     }
 }
 
@@ -355,14 +735,147 @@ where
     /// See the [`Print::h1`] and [`Print::h2`] methods for more details.
     #[must_use]
     pub fn new(io: W) -> Self {
+        let mut write = ParagraphInspectWrite::new(io);
+        write.color = ColorChoice::Auto.enabled();
         Self {
-            state: state::Header {
-                write: ParagraphInspectWrite::new(io),
-            },
             started: None,
+            events: None,
+            secrets: write.secrets.clone(),
+            warnings: Warnings::default(),
+            sections: SectionTimes::default(),
+            clock: SystemClock,
+            state: state::Header { write },
+        }
+    }
+}
+
+impl<W, C> Print<state::Header<W>, C>
+where
+    W: Write,
+    C: Clock,
+{
+    /// Choose the clock used for all durations and timer dots. Defaults to the
+    /// [`SystemClock`].
+    ///
+    /// See [`Clock`] for an example.
+    #[must_use]
+    pub fn clock<T: Clock>(self, clock: T) -> Print<state::Header<W>, T> {
+        Print {
+            started: self.started,
+            events: self.events,
+            secrets: self.secrets,
+            warnings: self.warnings,
+            sections: self.sections,
+            clock,
+            state: self.state,
         }
     }
 
+    /// Choose whether ANSI escape codes are emitted.
+    ///
+    /// Defaults to [`ColorChoice::Auto`], which follows the `NO_COLOR`, `CLICOLOR_FORCE` and
+    /// `TERM=dumb` conventions. When colors are disabled, escape codes are also removed from
+    /// messages decorated with the [`style`] helpers before they are written.
+    ///
+    /// ```rust
+    /// use bullet_stream::{style, ColorChoice, Print};
+    ///
+    /// let io = Print::new(Vec::new())
+    ///     .color(ColorChoice::Never)
+    ///     .h2("Example Buildpack")
+    ///     .bullet(format!("Ruby version {}", style::value("3.1.3")))
+    ///     .done()
+    ///     .done();
+    ///
+    /// assert!(!String::from_utf8_lossy(&io).contains("\x1B"));
+    /// ```
+    #[must_use]
+    pub fn color(mut self, choice: ColorChoice) -> Self {
+        self.state.write.color = choice.enabled();
+        self
+    }
+
+    /// Mirror every transition to a second writer as JSON Lines.
+    ///
+    /// The human readable output is unchanged. In addition, one JSON object is written to the
+    /// given writer for each header, bullet, sub bullet, stream, timer, paragraph and done
+    /// transition. This is intended for build platforms that want to index the output without
+    /// scraping it.
+    ///
+    /// Each object contains:
+    ///
+    /// - `kind`: One of `h1`, `h2`, `bullet`, `sub_bullet`, `stream_start`, `stream_done`,
+    ///   `timer_start`, `timer_done`, `timer_cancel`, `tasks_start`, `tasks_done`,
+    ///   `progress_start`, `progress_done`, `progress_cancel`, `warning`, `warning_suppressed`,
+    ///   `important`, `error`, `section_done` or `done`.
+    /// - `text`: The message with ANSI escape codes removed.
+    /// - `depth`: `0` for headers and paragraphs, `1` for bullets, `2` for sub bullets and one
+    ///   more for each nested [`Print::section`].
+    /// - `timestamp_ms`: Milliseconds since the Unix epoch when the event was emitted.
+    /// - `duration_ms`: Only present on events that finish something, such as a timer.
+    ///
+    /// Failing to write an event does not interrupt the human readable output.
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    ///
+    /// let events = tempfile::NamedTempFile::new().unwrap();
+    /// Print::new(std::io::stdout())
+    ///     .event_stream(events.reopen().unwrap())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Ruby version")
+    ///     .done()
+    ///     .done();
+    ///
+    /// let contents = std::fs::read_to_string(events.path()).unwrap();
+    /// assert!(contents.starts_with(r#"{"kind":"h2","text":"Example Buildpack","depth":0"#));
+    /// ```
+    #[must_use]
+    pub fn event_stream(mut self, io: impl Write + Send + 'static) -> Self {
+        self.events = Some(EventLog::new(io));
+        self
+    }
+
+    /// Write an error paragraph instead of a raw panic message when the program panics.
+    ///
+    /// Without it, a panic while a timer is running leaves half a line of dots followed by the
+    /// default panic message. With it, the panic message is kept until this output is dropped
+    /// as the panic unwinds: the timer is stopped, its line is finished and the message and
+    /// location are written as an [`Print::error`]:
+    ///
+    /// ```text
+    /// - Installing
+    ///   - Downloading ...
+    ///
+    /// ! Internal error: panicked at src/main.rs:10:5
+    /// !
+    /// ! Download failed
+    /// ```
+    ///
+    /// Only panics on the thread that last printed with this output are written this way, since
+    /// only that thread drops it as the panic unwinds. Panics on other threads and programs built
+    /// with `panic = "abort"` print the default message. The panic hook is installed once and
+    /// calls the previously installed hook in those cases. A panic that is caught before it
+    /// reaches the output, such as with `std::panic::catch_unwind`, is printed to stderr the next
+    /// time the output is used or dropped.
+    ///
+    /// For panics that are written this way, hooks installed before this call, such as a crash
+    /// reporter, are skipped. When `RUST_BACKTRACE` is set they are still called, so the
+    /// backtrace is printed to stderr in addition to the error.
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    ///
+    /// let output = Print::new(std::io::stdout())
+    ///     .panic_guard()
+    ///     .h2("Example Buildpack");
+    /// ```
+    #[must_use]
+    pub fn panic_guard(mut self) -> Self {
+        self.state.write.panic_guard = Some(PanicGuard::new());
+        self
+    }
+
     /// Announce the start of the buildpack.
     ///
     /// The input should be the human-readable name of your buildpack. Most buildpack names include
@@ -376,8 +889,16 @@ where
     ///
     /// This function will transition your buildpack output to [`state::Bullet`].
     #[must_use]
-    pub fn h1(mut self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>> {
-        writeln_now(
+    pub fn h1(self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>, C> {
+        self.try_h1(buildpack_name)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::h1`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_h1(mut self, buildpack_name: impl AsRef<str>) -> PrintResult<state::Bullet<W>, C> {
+        self.emit(EventKind::H1, buildpack_name.as_ref(), 0, None);
+        let result = writeln_now(
             &mut self.state.write,
             ansi_escape::wrap_ansi_escape_each_line(
                 &ANSI::BoldPurple,
@@ -385,7 +906,7 @@ where
             ),
         );
 
-        self.without_header()
+        PrintError::from_io(self.without_header(), result)
     }
 
     /// Announce the start of the buildpack.
@@ -401,37 +922,46 @@ where
     ///
     /// This function will transition your buildpack output to [`state::Bullet`].
     #[must_use]
-    pub fn h2(mut self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>> {
-        if !self.state.write.was_paragraph {
-            writeln_now(&mut self.state.write, "");
-        }
+    pub fn h2(self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>, C> {
+        self.try_h2(buildpack_name)
+            .expect("Output error: UI writer closed")
+    }
 
-        writeln_now(
-            &mut self.state.write,
-            ansi_escape::wrap_ansi_escape_each_line(
-                &ANSI::BoldPurple,
-                format!("## {}\n", buildpack_name.as_ref().trim()),
-            ),
-        );
+    /// Like [`Print::h2`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_h2(mut self, buildpack_name: impl AsRef<str>) -> PrintResult<state::Bullet<W>, C> {
+        self.emit(EventKind::H2, buildpack_name.as_ref(), 0, None);
+        let io = &mut self.state.write;
+        let result = if io.was_paragraph {
+            Ok(())
+        } else {
+            writeln_now(io, "")
+        }
+        .and_then(|()| {
+            writeln_now(
+                io,
+                ansi_escape::wrap_ansi_escape_each_line(
+                    &ANSI::BoldPurple,
+                    format!("## {}\n", buildpack_name.as_ref().trim()),
+                ),
+            )
+        });
 
-        self.without_header()
+        PrintError::from_io(self.without_header(), result)
     }
 
     /// Start a buildpack output without announcing the name.
     #[must_use]
-    pub fn without_header(self) -> Print<state::Bullet<W>> {
-        Print {
-            started: Some(Instant::now()),
-            state: state::Bullet {
-                write: self.state.write,
-            },
-        }
+    pub fn without_header(mut self) -> Print<state::Bullet<W>, C> {
+        self.started = Some(self.clock.now());
+        self.map_state(|state| state::Bullet { write: state.write })
     }
 }
 
-impl<W> Print<state::Bullet<W>>
+impl<W, C> Print<state::Bullet<W>, C>
 where
     W: Write + Send + Sync + 'static,
+    C: Clock,
 {
     const PREFIX_FIRST: &'static str = "- ";
     const PREFIX_REST: &'static str = "  ";
@@ -451,56 +981,91 @@ where
     ///
     /// This function will transition your buildpack output to [`state::SubBullet`].
     #[must_use]
-    pub fn bullet(mut self, s: impl AsRef<str>) -> Print<state::SubBullet<W>> {
-        writeln_now(&mut self.state.write, Self::style(s));
+    pub fn bullet(self, s: impl AsRef<str>) -> Print<state::SubBullet<W>, C> {
+        self.try_bullet(s).expect("Output error: UI writer closed")
+    }
 
-        Print {
-            started: self.started,
-            state: state::SubBullet {
-                write: self.state.write,
-            },
-        }
+    /// Like [`Print::bullet`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_bullet(mut self, s: impl AsRef<str>) -> PrintResult<state::SubBullet<W>, C> {
+        self.emit(EventKind::Bullet, s.as_ref(), 1, None);
+        self.sections.start(s.as_ref(), self.clock.now());
+        let result = writeln_now(&mut self.state.write, Self::style(s));
+
+        PrintError::from_io(
+            self.map_state(|state| state::SubBullet {
+                write: state.write,
+                parent: PhantomData,
+            }),
+            result,
+        )
     }
 
     /// Outputs an H2 header
     #[must_use]
-    pub fn h2(mut self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>> {
-        if !self.state.write.was_paragraph {
-            writeln_now(&mut self.state.write, "");
-        }
+    pub fn h2(self, buildpack_name: impl AsRef<str>) -> Print<state::Bullet<W>, C> {
+        self.try_h2(buildpack_name)
+            .expect("Output error: UI writer closed")
+    }
 
-        writeln_now(
-            &mut self.state.write,
-            ansi_escape::wrap_ansi_escape_each_line(
-                &ANSI::BoldPurple,
-                format!("## {}\n", buildpack_name.as_ref().trim()),
-            ),
-        );
+    /// Like [`Print::h2`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_h2(mut self, buildpack_name: impl AsRef<str>) -> PrintResult<state::Bullet<W>, C> {
+        self.emit(EventKind::H2, buildpack_name.as_ref(), 0, None);
+        let io = &mut self.state.write;
+        let result = if io.was_paragraph {
+            Ok(())
+        } else {
+            writeln_now(io, "")
+        }
+        .and_then(|()| {
+            writeln_now(
+                io,
+                ansi_escape::wrap_ansi_escape_each_line(
+                    &ANSI::BoldPurple,
+                    format!("## {}\n", buildpack_name.as_ref().trim()),
+                ),
+            )
+        });
 
-        self
+        PrintError::from_io(self, result)
     }
 
     /// Announce that your buildpack has finished execution successfully.
-    pub fn done(mut self) -> W {
-        if let Some(started) = &self.started {
-            let elapsed = duration_format::human(&started.elapsed());
+    ///
+    /// To repeat warnings and list how long each bullet took, use [`Print::done_with_summary`].
+    pub fn done(self) -> W {
+        self.try_done().expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::done`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_done(mut self) -> Result<W, PrintError<W>> {
+        let duration = self.started.map(|started| self.clock.elapsed(started));
+        self.emit(EventKind::Done, "Done", 0, duration);
+        let result = if let Some(duration) = &duration {
+            let elapsed = duration_format::human(duration);
             let details = style::details(format!("finished in {elapsed}"));
             writeln_now(
                 &mut self.state.write,
                 Self::style(format!("Done {details}")),
-            );
+            )
         } else {
-            writeln_now(&mut self.state.write, Self::style("Done"));
-        }
+            writeln_now(&mut self.state.write, Self::style("Done"))
+        };
 
-        self.state.write.inner
+        PrintError::from_io(self.state.write.into_inner(), result)
     }
 }
 
-impl<W> Print<state::Background<W>>
+impl<W, P, C> Print<state::Background<W, P>, C>
 where
     W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
 {
+    const DEPTH: usize = <state::SubBullet<W, P> as private::Sealed<W>>::DEPTH;
+
     /// Interrupt a timer with a message explaining why
     ///
     /// ```rust
@@ -533,19 +1098,40 @@ where
     ///     strip_ansi(String::from_utf8_lossy(&output.done())).trim()
     /// );
     /// ```
-    pub fn cancel(self, why_details: impl AsRef<str>) -> Print<state::SubBullet<W>> {
-        let mut io = match self.state.write.stop() {
-            Ok(io) => io,
-            // Stdlib docs recommend using `resume_unwind` to resume the thread panic
-            // <https://doc.rust-lang.org/std/thread/type.Result.html>
-            Err(e) => std::panic::resume_unwind(e),
-        };
+    pub fn cancel(self, why_details: impl AsRef<str>) -> Print<state::SubBullet<W, P>, C> {
+        self.try_cancel(why_details)
+            .expect("Output error: UI writer closed")
+    }
 
-        writeln_now(&mut io, style::details(why_details));
-        Print {
-            started: self.started,
-            state: state::SubBullet { write: io },
-        }
+    /// Like [`Print::cancel`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_cancel(
+        self,
+        why_details: impl AsRef<str>,
+    ) -> PrintResult<state::SubBullet<W, P>, C> {
+        self.emit(
+            EventKind::TimerCancel,
+            why_details.as_ref(),
+            Self::DEPTH,
+            Some(self.clock.elapsed(self.state.started)),
+        );
+        let mut result = Ok(());
+        let output = self.map_state(|state| {
+            let (mut io, timer_result) = match state.write.stop() {
+                Ok(stopped) => stopped,
+                // Stdlib docs recommend using `resume_unwind` to resume the thread panic
+                // <https://doc.rust-lang.org/std/thread/type.Result.html>
+                Err(e) => std::panic::resume_unwind(e),
+            };
+
+            result = timer_result.and_then(|()| writeln_now(&mut io, style::details(why_details)));
+            state::SubBullet {
+                write: io,
+                parent: PhantomData,
+            }
+        });
+
+        PrintError::from_io(output, result)
     }
 
     /// Finalize a timer's output.
@@ -553,33 +1139,56 @@ where
     /// Once you're finished with your long running task, calling this function
     /// finalizes the timer's output and transitions back to a [`state::SubBullet`].
     #[must_use]
-    pub fn done(self) -> Print<state::SubBullet<W>> {
-        let duration = self.state.started.elapsed();
-        let mut io = match self.state.write.stop() {
-            Ok(io) => io,
-            // Stdlib docs recommend using `resume_unwind` to resume the thread panic
-            // <https://doc.rust-lang.org/std/thread/type.Result.html>
-            Err(e) => std::panic::resume_unwind(e),
-        };
+    pub fn done(self) -> Print<state::SubBullet<W, P>, C> {
+        self.try_done().expect("Output error: UI writer closed")
+    }
 
-        writeln_now(&mut io, style::details(duration_format::human(&duration)));
-        Print {
-            started: self.started,
-            state: state::SubBullet { write: io },
-        }
+    /// Like [`Print::done`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_done(self) -> PrintResult<state::SubBullet<W, P>, C> {
+        let duration = self.clock.elapsed(self.state.started);
+        self.emit(EventKind::TimerDone, "Done", Self::DEPTH, Some(duration));
+        let mut result = Ok(());
+        let output = self.map_state(|state| {
+            let (mut io, timer_result) = match state.write.stop() {
+                Ok(stopped) => stopped,
+                // Stdlib docs recommend using `resume_unwind` to resume the thread panic
+                // <https://doc.rust-lang.org/std/thread/type.Result.html>
+                Err(e) => std::panic::resume_unwind(e),
+            };
+
+            result = timer_result.and_then(|()| {
+                writeln_now(&mut io, style::details(duration_format::human(&duration)))
+            });
+            state::SubBullet {
+                write: io,
+                parent: PhantomData,
+            }
+        });
+
+        PrintError::from_io(output, result)
     }
 }
 
-impl<W> Print<state::SubBullet<W>>
+impl<W, P, C> Print<state::SubBullet<W, P>, C>
 where
     W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
 {
-    const PREFIX_FIRST: &'static str = "  - ";
-    const PREFIX_REST: &'static str = "    ";
-    const CMD_INDENT: &'static str = "      ";
+    const DEPTH: usize = <state::SubBullet<W, P> as private::Sealed<W>>::DEPTH;
 
     fn style(s: impl AsRef<str>) -> String {
-        prefix_first_rest_lines(Self::PREFIX_FIRST, Self::PREFIX_REST, s.as_ref().trim())
+        let indent = "  ".repeat(Self::DEPTH - 1);
+        prefix_first_rest_lines(
+            &format!("{indent}- "),
+            &format!("{indent}  "),
+            s.as_ref().trim(),
+        )
+    }
+
+    fn cmd_indent() -> String {
+        "  ".repeat(Self::DEPTH + 1)
     }
 
     /// Emit a sub bullet point step in the output under a bullet point.
@@ -606,9 +1215,49 @@ where
     ///
     /// Multiple steps are allowed within a section. This function returns to the same [`state::SubBullet`].
     #[must_use]
-    pub fn sub_bullet(mut self, s: impl AsRef<str>) -> Print<state::SubBullet<W>> {
-        writeln_now(&mut self.state.write, Self::style(s));
-        self
+    pub fn sub_bullet(self, s: impl AsRef<str>) -> Print<state::SubBullet<W, P>, C> {
+        self.try_sub_bullet(s)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::sub_bullet`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_sub_bullet(mut self, s: impl AsRef<str>) -> PrintResult<state::SubBullet<W, P>, C> {
+        self.emit(EventKind::SubBullet, s.as_ref(), Self::DEPTH, None);
+        let result = writeln_now(&mut self.state.write, Self::style(s));
+        PrintError::from_io(self, result)
+    }
+
+    /// Open a child section, indented one level deeper than the current one.
+    ///
+    /// A child section is useful when a step has steps of its own, for example installing
+    /// each workspace of a monorepo. The child supports everything a [`state::SubBullet`]
+    /// does, including opening its own child sections. Calling `done()` on the child
+    /// transitions back to this section.
+    #[must_use]
+    pub fn section(
+        self,
+        s: impl AsRef<str>,
+    ) -> Print<state::SubBullet<W, state::SubBullet<W, P>>, C> {
+        self.try_section(s).expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::section`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_section(
+        mut self,
+        s: impl AsRef<str>,
+    ) -> PrintResult<state::SubBullet<W, state::SubBullet<W, P>>, C> {
+        self.emit(EventKind::Bullet, s.as_ref(), Self::DEPTH, None);
+        let result = writeln_now(&mut self.state.write, Self::style(s));
+
+        PrintError::from_io(
+            self.map_state(|state| state::SubBullet {
+                write: state.write,
+                parent: PhantomData,
+            }),
+            result,
+        )
     }
 
     /// Stream output to the end user.
@@ -617,33 +1266,65 @@ where
     /// end user. Streaming lets the end user know that something is happening and provides them with
     /// the output of the process.
     ///
-    /// The result of this function is a `Print<state::Stream<W>>` which implements [`std::io::Write`].
+    /// The result of this function is a `Print<state::Stream<W, P>>` which implements [`std::io::Write`].
     ///
     /// If you do not wish the end user to view the output of the process, consider using a `step` instead.
     ///
     /// This function will transition your buildpack output to [`state::Stream`].
     #[must_use]
-    pub fn start_stream(mut self, s: impl AsRef<str>) -> Print<state::Stream<W>> {
-        writeln_now(&mut self.state.write, Self::style(s));
-        writeln_now(&mut self.state.write, "");
+    pub fn start_stream(self, s: impl AsRef<str>) -> Print<state::Stream<W, P>, C> {
+        self.try_start_stream(s)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_stream`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    // The error carries the stream so the caller can keep using it, boxing it would only
+    // make that harder.
+    #[allow(clippy::result_large_err)]
+    pub fn try_start_stream(self, s: impl AsRef<str>) -> PrintResult<state::Stream<W, P>, C> {
+        self.try_start_stream_with_options(s, StreamOptions::default())
+    }
+
+    /// Like [`Print::start_stream`], but processes the streamed output according to the
+    /// given [`StreamOptions`].
+    #[must_use]
+    pub fn start_stream_with_options(
+        self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+    ) -> Print<state::Stream<W, P>, C> {
+        self.try_start_stream_with_options(s, options)
+            .expect("Output error: UI writer closed")
+    }
 
-        Print {
-            started: self.started,
-            state: state::Stream {
-                started: Instant::now(),
-                write: line_mapped(self.state.write, |mut line| {
-                    // Avoid adding trailing whitespace to the line, if there was none already.
-                    // The `[b'\n']` case is required since `line` includes the trailing newline byte.
-                    if line.is_empty() || line == [b'\n'] {
-                        line
-                    } else {
-                        let mut result: Vec<u8> = Self::CMD_INDENT.into();
-                        result.append(&mut line);
-                        result
-                    }
-                }),
-            },
-        }
+    /// Like [`Print::start_stream_with_options`], but returns a [`PrintError`] instead of
+    /// panicking when the output cannot be written.
+    #[allow(clippy::result_large_err)]
+    pub fn try_start_stream_with_options(
+        mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+    ) -> PrintResult<state::Stream<W, P>, C> {
+        self.emit(EventKind::StreamStart, s.as_ref(), Self::DEPTH, None);
+        let result = writeln_now(&mut self.state.write, Self::style(s))
+            .and_then(|()| writeln_now(&mut self.state.write, ""));
+
+        let started = self.clock.now();
+        let heartbeat = self.heartbeat(&options);
+        let filter = Arc::new(LineFilter::new(&options));
+        let output = self.map_state(|state| state::Stream {
+            started,
+            write: Self::format_stream_writer(
+                state.write.secrets.clone(),
+                HeartbeatWrite::new(state.write, heartbeat),
+                options,
+                Arc::clone(&filter),
+            ),
+            filter,
+            parent: PhantomData,
+        });
+        PrintError::from_io(output, result)
     }
 
     /// Output periodic timer updates to the end user.
@@ -658,46 +1339,166 @@ where
     /// This function will transition your buildpack output to [`state::Background`].
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
-    pub fn start_timer(mut self, s: impl AsRef<str>) -> Print<state::Background<W>> {
+    pub fn start_timer(self, s: impl AsRef<str>) -> Print<state::Background<W, P>, C> {
+        self.try_start_timer(s)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_timer`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_start_timer(self, s: impl AsRef<str>) -> PrintResult<state::Background<W, P>, C> {
+        self.try_start_timer_with_options(s, TimerOptions::default())
+    }
+
+    /// Like [`Print::start_timer`], but the ticks are printed according to the given
+    /// [`TimerOptions`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn start_timer_with_options(
+        self,
+        s: impl AsRef<str>,
+        options: TimerOptions,
+    ) -> Print<state::Background<W, P>, C> {
+        self.try_start_timer_with_options(s, options)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_timer_with_options`], but returns a [`PrintError`] instead of
+    /// panicking when the output cannot be written.
+    pub fn try_start_timer_with_options(
+        self,
+        s: impl AsRef<str>,
+        options: TimerOptions,
+    ) -> PrintResult<state::Background<W, P>, C> {
+        self.try_start_timer_with_driver(s, &options, background_printer::Driver::Thread)
+    }
+
+    fn try_start_timer_with_driver(
+        mut self,
+        s: impl AsRef<str>,
+        options: &TimerOptions,
+        driver: background_printer::Driver,
+    ) -> PrintResult<state::Background<W, P>, C> {
+        self.emit(EventKind::TimerStart, s.as_ref(), Self::DEPTH, None);
         // Do not emit a newline after the message
-        write!(self.state.write, "{}", Self::style(s)).expect("Output error: UI writer closed");
-        self.state
-            .write
-            .flush()
-            .expect("Output error: UI writer closed");
+        let line = self.state.write.secrets.redact(Self::style(s));
+        let line = if self.state.write.color {
+            line
+        } else {
+            strip_ansi(line)
+        };
+        let result = write!(self.state.write, "{line}").and_then(|()| self.state.write.flush());
 
-        Print {
-            started: self.started,
-            state: state::Background {
-                started: Instant::now(),
-                write: background_printer::print_interval(
-                    self.state.write,
-                    std::time::Duration::from_secs(1),
-                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, " ."),
-                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, "."),
-                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, ". "),
-                ),
-            },
+        let color = self.state.write.color;
+        let (start, tick, end) = options.ticks(move |tick: &str| {
+            if color {
+                ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, tick)
+            } else {
+                String::from(tick)
+            }
+        });
+
+        let started = self.clock.now();
+        let clock = self.clock.clone();
+        let output = self.map_state(|state| state::Background {
+            started,
+            write: background_printer::print_interval(
+                state.write,
+                clock,
+                options.schedule(),
+                start,
+                tick,
+                end,
+                driver,
+            ),
+            parent: PhantomData,
+        });
+        PrintError::from_io(output, result)
+    }
+
+    /// The heartbeat of a stream, when it's enabled in the options.
+    fn heartbeat(&self, options: &StreamOptions) -> Option<Heartbeat> {
+        let color = self.state.write.color;
+        let indent = Self::cmd_indent();
+        options.heartbeat.map(|quiet_period| {
+            Heartbeat::new(self.clock.clone(), quiet_period, move |elapsed| {
+                let line = format!(
+                    "(still running, {} elapsed)",
+                    duration_format::compact(&elapsed)
+                );
+                let line = if color {
+                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, line)
+                } else {
+                    line
+                };
+                format!("{indent}{line}\n")
+            })
+        })
+    }
+
+    /// The prefix of each line written by the given source, see [`StreamOptions::mark_stderr`].
+    ///
+    /// A prefix without colors of its own is dim.
+    fn stream_prefix(&self, options: &StreamOptions, source: Source) -> String {
+        match (source, &options.stderr_prefix) {
+            (Source::Stderr, Some(prefix)) if !self.state.write.color => strip_ansi(prefix),
+            (Source::Stderr, Some(prefix)) if strip_ansi(prefix) == *prefix => {
+                ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, prefix)
+            }
+            (Source::Stderr, Some(prefix)) => prefix.clone(),
+            _ => String::new(),
         }
     }
 
-    fn format_stream_writer<S>(stream_to: S) -> crate::write::MappedWrite<S>
+    /// Processes each line according to the `options` and redacts it. The lines still need to
+    /// be formatted with [`Print::format_stream_line`].
+    fn map_stream_writer<S>(
+        secrets: Secrets,
+        stream_to: S,
+        options: StreamOptions,
+    ) -> crate::write::MappedWrite<S>
     where
         S: Write + Send + Sync,
     {
-        line_mapped(stream_to, |mut line| {
-            // Avoid adding trailing whitespace to the line, if there was none already.
-            // The `[b'\n']` case is required since `line` includes the trailing newline byte.
-            if line.is_empty() || line == [b'\n'] {
-                line
-            } else {
-                let mut result: Vec<u8> = Self::CMD_INDENT.into();
-                result.append(&mut line);
-                result
-            }
+        line_mapped(stream_to, move |line| {
+            secrets.redact_bytes(options.map_line(line))
+        })
+    }
+
+    fn format_stream_writer<S>(
+        secrets: Secrets,
+        stream_to: S,
+        options: StreamOptions,
+        filter: Arc<LineFilter>,
+    ) -> crate::write::MappedWrite<S>
+    where
+        S: Write + Send + Sync,
+    {
+        line_mapped(stream_to, move |line| {
+            Self::format_stream_line(secrets.redact_bytes(options.map_line(line)), "", &filter)
         })
     }
 
+    /// Marks a redacted line with the `prefix` of its source, copies it to the transcript and
+    /// indents it. Returns an empty line if the filter holds it back.
+    fn format_stream_line(mut line: Vec<u8>, prefix: &str, filter: &LineFilter) -> Vec<u8> {
+        // Avoid adding trailing whitespace to the line, if there was none already.
+        // The `[b'\n']` case is required since `line` includes the trailing newline byte.
+        let blank = line.is_empty() || line == [b'\n'];
+        if !blank {
+            line.splice(0..0, prefix.bytes());
+        }
+        filter.transcribe(&line);
+        let line = if blank {
+            line
+        } else {
+            let mut result: Vec<u8> = Self::cmd_indent().into();
+            result.append(&mut line);
+            result
+        };
+        filter.collapse(line)
+    }
+
     /// Stream two inputs without consuming
     ///
     /// The `start_stream` returns a single writer, but running a command often requires two.
@@ -730,95 +1531,227 @@ where
     /// output.done().done();
     /// ```
     #[allow(clippy::missing_panics_doc)]
-    pub fn stream_with<F, T>(&mut self, s: impl AsRef<str>, mut f: F) -> T
+    pub fn stream_with<F, T>(&mut self, s: impl AsRef<str>, f: F) -> T
     where
         F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
         T: 'static,
     {
-        writeln_now(&mut self.state.write, Self::style(s));
-        writeln_now(&mut self.state.write, "");
+        self.try_stream_with(s, f)
+            .expect("Output error: UI writer closed")
+    }
 
-        let duration = Instant::now();
-        mpsc_stream_to_output(
+    /// Like [`Print::stream_with`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    ///
+    /// The closure is always called, even when the output cannot be written. Its return value
+    /// is available from the error via [`PrintError::into_inner`].
+    pub fn try_stream_with<F, T>(&mut self, s: impl AsRef<str>, f: F) -> Result<T, PrintError<T>>
+    where
+        F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
+        T: 'static,
+    {
+        self.try_stream_with_options(s, StreamOptions::default(), f)
+    }
+
+    /// Like [`Print::stream_with`], but processes the streamed output according to the
+    /// given [`StreamOptions`].
+    #[allow(clippy::missing_panics_doc)]
+    pub fn stream_with_options<F, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        f: F,
+    ) -> T
+    where
+        F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
+        T: 'static,
+    {
+        self.try_stream_with_options(s, options, f)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::stream_with_options`], but returns a [`PrintError`] instead of panicking
+    /// when the output cannot be written.
+    ///
+    /// The closure is always called, even when the output cannot be written. Its return value
+    /// is available from the error via [`PrintError::into_inner`].
+    pub fn try_stream_with_options<F, T>(
+        &mut self,
+        s: impl AsRef<str>,
+        options: StreamOptions,
+        mut f: F,
+    ) -> Result<T, PrintError<T>>
+    where
+        F: FnMut(Box<dyn Write + Send + Sync>, Box<dyn Write + Send + Sync>) -> T,
+        T: 'static,
+    {
+        let mut result = self.start_stream_with(s);
+        let started = self.clock.now();
+        let secrets = self.secrets.clone();
+        let mut heartbeat = self.heartbeat(&options);
+        let filter = &LineFilter::new(&options);
+        let stdout_prefix = self.stream_prefix(&options, Source::Stdout);
+        let stderr_prefix = self.stream_prefix(&options, Source::Stderr);
+        let write_result = &mut result;
+        let value = mpsc_stream_to_output(
             |sender| {
                 f(
                     // The Senders are boxed to hide the types from the caller so it can be changed
                     // in the future. They only need to know they have a `Write + Send + Sync` type.
-                    Box::new(Self::format_stream_writer(sender.clone())),
-                    Box::new(Self::format_stream_writer(sender.clone())),
+                    Box::new(Self::map_stream_writer(
+                        secrets.clone(),
+                        sender.clone(),
+                        options.clone(),
+                    )),
+                    Box::new(Self::map_stream_writer(
+                        secrets.clone(),
+                        sender.with_source(Source::Stderr),
+                        options.clone(),
+                    )),
                 )
             },
             move |recv| {
                 // When it receives input, it writes it to the current `Write` value.
                 //
-                // When the senders close their channel this loop will exit
-                for message in recv {
-                    self.state
-                        .write
-                        .write_all(&message)
-                        .expect("Writer to not be closed");
-                }
-
-                if !self.state.write_mut().was_paragraph {
-                    writeln_now(&mut self.state.write, "");
+                // When the senders close their channel this loop will exit. After a write
+                // error, keep receiving so the senders never observe a closed channel.
+                loop {
+                    let message = heartbeat::recv(&recv, heartbeat.as_mut(), |line| {
+                        if write_result.is_ok() {
+                            *write_result = self.state.write.write_all(line.as_bytes());
+                        }
+                    });
+                    let Some((source, message)) = message else {
+                        break;
+                    };
+                    let prefix = match source {
+                        Source::Stdout => &stdout_prefix,
+                        Source::Stderr => &stderr_prefix,
+                    };
+                    let line = Self::format_stream_line(message, prefix, filter);
+                    if write_result.is_ok() {
+                        *write_result = self.state.write.write_all(&line);
+                    }
                 }
 
-                writeln_now(
-                    &mut self.state.write,
-                    Self::style(format!(
-                        "Done {}",
-                        style::details(duration_format::human(&duration.elapsed()))
-                    )),
-                );
+                self.finish_stream_with(started, filter, write_result);
             },
-        )
+        );
+        PrintError::from_io(value, result)
+    }
+
+    /// Announce the start of a `stream_with` block.
+    fn start_stream_with(&mut self, s: impl AsRef<str>) -> std::io::Result<()> {
+        self.emit(EventKind::StreamStart, s.as_ref(), Self::DEPTH, None);
+        writeln_now(&mut self.state.write, Self::style(s))
+            .and_then(|()| writeln_now(&mut self.state.write, ""))
+    }
+
+    /// Announce the end of a `stream_with` block once all streamed output has been written.
+    ///
+    /// Nothing is written if a prior write failed.
+    fn finish_stream_with(
+        &mut self,
+        started: Instant,
+        filter: &LineFilter,
+        result: &mut std::io::Result<()>,
+    ) {
+        let duration = self.clock.elapsed(started);
+        self.emit(EventKind::StreamDone, "Done", Self::DEPTH, Some(duration));
+        if result.is_ok() {
+            *result = Self::finish_stream(self.state.write_mut(), filter, duration);
+        }
+    }
+
+    /// Write the lines that were held back by the [`StreamOptions`], then the `Done` line of a
+    /// stream.
+    fn finish_stream(
+        io: &mut ParagraphInspectWrite<W>,
+        filter: &LineFilter,
+        duration: Duration,
+    ) -> std::io::Result<()> {
+        let finished = filter.finish();
+        if finished.hidden > 0 {
+            let marker = line_filter::hidden_marker(finished.hidden);
+            writeln_now(
+                io,
+                format!(
+                    "{}{}",
+                    Self::cmd_indent(),
+                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, marker)
+                ),
+            )?;
+        }
+        for line in finished.tail {
+            io.write_all(&line)?;
+        }
+        io.flush()?;
+
+        if !io.was_paragraph {
+            writeln_now(io, "")?;
+        }
+        let mut details = duration_format::human(&duration);
+        if let Some(path) = finished.transcript {
+            details = format!("{details}, full output in {}", path.display());
+        }
+        writeln_now(io, Self::style(format!("Done {}", style::details(details))))
     }
 
-    /// Finish a section and transition back to [`state::Bullet`].
+    /// Finish a section and transition back to its parent, usually a [`state::Bullet`].
     #[must_use]
-    pub fn done(self) -> Print<state::Bullet<W>> {
-        Print {
-            started: self.started,
-            state: state::Bullet {
-                write: self.state.write,
-            },
+    pub fn done(mut self) -> Print<P, C> {
+        self.emit(EventKind::SectionDone, "Done", Self::DEPTH - 1, None);
+        if P::DEPTH == 1 {
+            self.sections.finish(&self.clock);
         }
+        self.map_state(|state| P::from_write(state.write))
     }
 }
 
-impl<W> Print<state::Stream<W>>
+impl<W, P, C> Print<state::Stream<W, P>, C>
 where
     W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
 {
     /// Finalize a stream's output
     ///
     /// Once you're finished streaming to the output, calling this function
     /// finalizes the stream's output and transitions back to a [`state::Bullet`].
     #[must_use]
-    pub fn done(self) -> Print<state::SubBullet<W>> {
-        let duration = self.state.started.elapsed();
-
-        let mut output = Print {
-            started: self.started,
-            state: state::SubBullet {
-                write: self.state.write.unwrap(),
-            },
-        };
+    pub fn done(self) -> Print<state::SubBullet<W, P>, C> {
+        self.try_done().expect("Output error: UI writer closed")
+    }
 
-        if !output.state.write_mut().was_paragraph {
-            writeln_now(&mut output.state.write, "");
-        }
+    /// Like [`Print::done`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_done(self) -> PrintResult<state::SubBullet<W, P>, C> {
+        let duration = self.clock.elapsed(self.state.started);
+        self.emit(
+            EventKind::StreamDone,
+            "Done",
+            <state::SubBullet<W, P> as private::Sealed<W>>::DEPTH,
+            Some(duration),
+        );
 
-        output.sub_bullet(format!(
-            "Done {}",
-            style::details(duration_format::human(&duration))
-        ))
+        let mut result = Ok(());
+        let output = self.map_state(|state| {
+            let mut io = state.write.unwrap().into_inner();
+            result =
+                Print::<state::SubBullet<W, P>, C>::finish_stream(&mut io, &state.filter, duration);
+            state::SubBullet {
+                write: io,
+                parent: PhantomData,
+            }
+        });
+        PrintError::from_io(output, result)
     }
 }
 
-impl<W> Write for Print<state::Stream<W>>
+impl<W, P, C> Write for Print<state::Stream<W, P>, C>
 where
     W: Write,
+    C: Clock,
 {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.state.write.write(buf)
@@ -829,17 +1762,63 @@ where
     }
 }
 
+/// Internal helper, writes a paragraph such as an error with each line prefixed by `!`.
+fn write_paragraph<W: Write>(
+    io: &mut ParagraphInspectWrite<W>,
+    color: &ANSI,
+    s: impl AsRef<str>,
+) -> std::io::Result<()> {
+    let contents = s.as_ref().trim();
+
+    if !io.was_paragraph {
+        writeln_now(io, "")?;
+    }
+
+    writeln_now(
+        io,
+        ansi_escape::wrap_ansi_escape_each_line(
+            color,
+            prefix_lines(contents, |_, line| {
+                // Avoid adding trailing whitespace to the line, if there was none already.
+                // The `\n` case is required since `prefix_lines` uses `str::split_inclusive`,
+                // which preserves any trailing newline characters if present.
+                if line.is_empty() || line == "\n" {
+                    String::from("!")
+                } else {
+                    String::from("! ")
+                }
+            }),
+        ),
+    )?;
+    writeln_now(io, "")
+}
+
 /// Internal helper, ensures that all contents are always flushed (never buffered).
-fn writeln_now<D: Write>(destination: &mut D, msg: impl AsRef<str>) {
-    writeln!(destination, "{}", msg.as_ref()).expect("Output error: UI writer closed");
+///
+/// Redacts secrets, and removes ANSI escape codes from the message when the destination has
+/// colors disabled.
+fn writeln_now<W: Write>(
+    destination: &mut ParagraphInspectWrite<W>,
+    msg: impl AsRef<str>,
+) -> std::io::Result<()> {
+    if let Some(guard) = destination.panic_guard.as_mut() {
+        guard.used();
+    }
+    let msg = destination.secrets.redact(msg);
+    if destination.color {
+        writeln!(destination, "{msg}")?;
+    } else {
+        writeln!(destination, "{}", strip_ansi(msg))?;
+    }
 
-    destination.flush().expect("Output error: UI writer closed");
+    destination.flush()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::util::LockedWriter;
+    use crate::testing::{print, Capture};
+    use crate::util::{ClosedWriter, LockedWriter};
     use ansi_escape::strip_ansi;
     use fun_run::CommandWithName;
     use indoc::formatdoc;
@@ -913,9 +1892,334 @@ mod test {
         assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
     }
 
+    #[test]
+    fn stream_heartbeat() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+        let mut stream = print(&capture, &clock)
+            .without_header()
+            .bullet("Compiling")
+            .start_stream_with_options(
+                "Running `make`",
+                // The background thread checks the clock every millisecond
+                StreamOptions::new().heartbeat(Duration::from_millis(1)),
+            );
+        writeln!(stream, "compiling").unwrap();
+        clock.advance(Duration::from_secs(5));
+
+        // Only the time it takes the thread to write the heartbeat depends on the system
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !capture.contents().contains("still running") && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        writeln!(stream, "linking").unwrap();
+        stream.done().done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Compiling
+                  - Running `make`
+
+                      compiling
+                      (still running, 5s elapsed)
+                      linking
+
+                  - Done (5.0s)
+                - Done (finished in 5.0s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn stream_with_marks_stderr() {
+        let mut output = Print::new(Vec::new()).without_header().bullet("Installing");
+        output.stream_with_options(
+            "Running `npm install`",
+            StreamOptions::new().mark_stderr("stderr: "),
+            |mut stdout, mut stderr| {
+                writeln!(stdout, "added 1 package").unwrap();
+                writeln!(stderr, "npm warn deprecated\n").unwrap();
+            },
+        );
+        let io = output.done().done();
+
+        let expected = formatdoc! {"
+            - Installing
+              - Running `npm install`
+
+                  added 1 package
+                  stderr: npm warn deprecated
+
+              - Done (< 0.1s)
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
+    #[test]
+    fn stream_with_styled_stderr_prefix() {
+        let stream = |prefix: String| {
+            let mut output = Print::new(Vec::new())
+                .color(ColorChoice::Always)
+                .without_header()
+                .bullet("Installing");
+            output.stream_with_options(
+                "Running `npm install`",
+                StreamOptions::new().mark_stderr(prefix),
+                |_, mut stderr| writeln!(stderr, "npm warn deprecated").unwrap(),
+            );
+            String::from_utf8(output.done().done()).unwrap()
+        };
+
+        let dim = ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, "stderr: ");
+        assert_contains!(stream("stderr: ".to_string()), &format!("{dim}npm warn"));
+
+        let important = style::important("stderr: ");
+        assert_contains!(stream(important.clone()), &format!("{important}npm warn"));
+    }
+
+    #[test]
+    fn stream_collapse_with_transcript() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let transcript = tmpdir.path().join("make.log");
+        let mut stream = Print::new(Vec::new())
+            .without_header()
+            .bullet("Compiling")
+            .start_stream_with_options(
+                "Running `make`",
+                StreamOptions::new().collapse(2, 1).transcript(&transcript),
+            );
+        for n in 1..=6 {
+            writeln!(stream, "line {n}").unwrap();
+        }
+        let io = stream.done().done().done();
+
+        let expected = formatdoc! {"
+            - Compiling
+              - Running `make`
+
+                  line 1
+                  line 2
+                  ... 3 lines hidden ...
+                  line 6
+
+              - Done (< 0.1s, full output in {})
+            - Done (finished in < 0.1s)
+        ", transcript.display()};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+        assert_eq!(
+            "line 1\nline 2\nline 3\nline 4\nline 5\nline 6\n",
+            std::fs::read_to_string(&transcript).unwrap()
+        );
+    }
+
+    #[test]
+    fn event_stream() {
+        let events = LockedWriter::new(Vec::new());
+        let mut stream = Print::new(Vec::new())
+            .event_stream(events.clone())
+            .h2("Example Buildpack")
+            .warning("Careful")
+            .bullet("Ruby version")
+            .sub_bullet("Using \"3.1.3\"")
+            .start_stream("Streaming");
+        writeln!(&mut stream, "hello").unwrap();
+        let io = stream
+            .done()
+            .start_timer("Installing")
+            .cancel("Interrupted")
+            .done()
+            .done();
+
+        let actual = String::from_utf8_lossy(&events.unwrap())
+            .lines()
+            .map(|line| {
+                // Remove non-deterministic values
+                let (before, _) = line.split_once(r#","timestamp_ms""#).unwrap();
+                let duration = if line.contains("duration_ms") {
+                    " duration_ms"
+                } else {
+                    ""
+                };
+                format!("{before}}}{duration}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let expected = formatdoc! {r#"
+            {{"kind":"h2","text":"Example Buildpack","depth":0}}
+            {{"kind":"warning","text":"Careful","depth":0}}
+            {{"kind":"bullet","text":"Ruby version","depth":1}}
+            {{"kind":"sub_bullet","text":"Using \"3.1.3\"","depth":2}}
+            {{"kind":"stream_start","text":"Streaming","depth":2}}
+            {{"kind":"stream_done","text":"Done","depth":2}} duration_ms
+            {{"kind":"timer_start","text":"Installing","depth":2}}
+            {{"kind":"timer_cancel","text":"Interrupted","depth":2}} duration_ms
+            {{"kind":"section_done","text":"Done","depth":1}}
+            {{"kind":"done","text":"Done","depth":0}} duration_ms
+        "#};
+        assert_eq!(expected.trim(), actual);
+
+        // Human readable output is unchanged
+        assert_contains!(
+            strip_ansi(String::from_utf8_lossy(&io)),
+            "  - Installing ... (Interrupted)\n"
+        );
+    }
+
+    #[test]
+    fn collapse_carriage_returns() {
+        let options = StreamOptions::new().collapse_carriage_returns(true);
+        let mut stream = Print::new(Vec::new())
+            .without_header()
+            .bullet("Downloading")
+            .start_stream_with_options("Running `curl`", options.clone());
+        write!(&mut stream, "10%\r50%").unwrap();
+        writeln!(&mut stream, "\r100%").unwrap();
+        writeln!(&mut stream, "Saved\r").unwrap();
+
+        let mut output = stream.done();
+        output.stream_with_options("Running `pip`", options, |mut stdout, _| {
+            write!(stdout, "1/2\r2/2\r\n").unwrap();
+        });
+        let io = output.done().done();
+
+        let expected = formatdoc! {"
+            - Downloading
+              - Running `curl`
+
+                  100%
+                  Saved
+
+              - Done (< 0.1s)
+              - Running `pip`
+
+                  2/2
+
+              - Done (< 0.1s)
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let events = tempfile::NamedTempFile::new().unwrap();
+        let output = Print::new(Vec::new())
+            .redact("hunter2")
+            .event_stream(events.reopen().unwrap())
+            .h2("Deploying with hunter2")
+            .bullet("Token hunter2")
+            .warning("Rotate hunter2")
+            .start_timer("Checking hunter2")
+            .done();
+
+        // Secrets registered in a later state still apply
+        let mut stream = output.redact("s3cr3t").start_stream("Uploading");
+        write!(&mut stream, "using hun").unwrap();
+        writeln!(&mut stream, "ter2").unwrap();
+
+        let mut output = stream.done();
+        output.stream_with("Retrying", |mut stdout, mut stderr| {
+            write!(stdout, "s3").unwrap();
+            writeln!(stdout, "cr3t").unwrap();
+            writeln!(stderr, "hunter2").unwrap();
+        });
+        let io = output.done().done();
+
+        let expected = formatdoc! {"
+
+            ## Deploying with [REDACTED]
+
+            - Token [REDACTED]
+
+            ! Rotate [REDACTED]
+
+              - Checking [REDACTED] ... (< 0.1s)
+              - Uploading
+
+                  using [REDACTED]
+
+              - Done (< 0.1s)
+              - Retrying
+
+                  [REDACTED]
+                  [REDACTED]
+
+              - Done (< 0.1s)
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+
+        let events = std::fs::read_to_string(events.path()).unwrap();
+        assert_contains!(events, "Deploying with [REDACTED]");
+        assert!(!events.contains("hunter2"));
+    }
+
+    #[test]
+    fn nested_sections() {
+        let workspace = Print::new(Vec::new())
+            .without_header()
+            .bullet("Workspaces")
+            .sub_bullet("Detected 2 workspaces")
+            .section("Workspace `api`")
+            .sub_bullet("Installing");
+
+        let mut stream = workspace
+            .section("Package `server`")
+            .start_timer("Downloading")
+            .done()
+            .start_stream("Compiling");
+        writeln!(&mut stream, "hello\nworld").unwrap();
+
+        let mut package = stream.done().warning("Deprecated");
+        package.stream_with("Testing", |mut stdout, _| {
+            writeln!(stdout, "ok").unwrap();
+        });
+
+        let io = package
+            .done()
+            .sub_bullet("Linking")
+            .done()
+            .section("Workspace `web`")
+            .done()
+            .done()
+            .done();
+
+        let expected = formatdoc! {"
+            - Workspaces
+              - Detected 2 workspaces
+              - Workspace `api`
+                - Installing
+                - Package `server`
+                  - Downloading ... (< 0.1s)
+                  - Compiling
+
+                      hello
+                      world
+
+                  - Done (< 0.1s)
+
+            ! Deprecated
+
+                  - Testing
+
+                      ok
+
+                  - Done (< 0.1s)
+                - Linking
+              - Workspace `web`
+            - Done (finished in < 0.1s)
+        "};
+
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
     #[test]
     fn background_timer() {
         let io = Print::new(Vec::new())
+            .color(ColorChoice::Always)
             .without_header()
             .bullet("Background")
             .start_timer("Installing")
@@ -976,6 +2280,7 @@ mod test {
         let path = tmpdir.path().join("output.txt");
 
         Print::new(File::create(&path).unwrap())
+            .color(ColorChoice::Always)
             .h1("Buildpack Header is Bold Purple")
             .important("Important is bold cyan")
             .warning("Warnings are yellow")
@@ -996,6 +2301,91 @@ mod test {
         assert_eq!(expected, std::fs::read_to_string(path).unwrap());
     }
 
+    #[test]
+    fn color_never() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("output.txt");
+
+        let mut stream = Print::new(File::create(&path).unwrap())
+            .color(ColorChoice::Never)
+            .h1("Buildpack Header")
+            .important("Important")
+            .bullet(format!("Ruby version {}", style::value("3.1.3")))
+            .start_timer("Installing")
+            .done()
+            .start_stream(format!("Running {}", style::command("bundle install")));
+
+        let unowned = style::value("unowned");
+        writeln!(&mut stream, "{unowned}").unwrap();
+        stream.done().done().error("Errors");
+
+        let actual = std::fs::read_to_string(path).unwrap();
+        // Only the streamed (unowned) output retains its escape codes
+        assert_eq!(2, actual.matches('\x1B').count());
+        assert_contains!(actual, "  - Installing ... (< 0.1s)\n");
+        assert_contains!(actual, "  - Running `bundle install`\n");
+        assert_contains!(actual, &unowned);
+    }
+
+    #[test]
+    fn try_transitions_return_state_on_error() {
+        let output = Print::new(ClosedWriter)
+            .try_h2("Example Buildpack")
+            .unwrap_err()
+            .into_inner();
+
+        let error = output.try_bullet("Ruby version").unwrap_err();
+        assert_eq!(std::io::ErrorKind::BrokenPipe, error.io_error().kind());
+
+        let timer = error
+            .into_inner()
+            .try_sub_bullet("Installing")
+            .unwrap_or_else(PrintError::into_inner)
+            .try_start_timer("Downloading")
+            .unwrap_or_else(PrintError::into_inner);
+
+        let mut bullet = timer.try_done().unwrap_or_else(PrintError::into_inner);
+        let value = bullet
+            .try_stream_with("Streaming", |mut stdout, _| {
+                writeln!(stdout, "hello").unwrap();
+                "closure value"
+            })
+            .unwrap_err()
+            .into_inner();
+        assert_eq!("closure value", value);
+
+        let result = bullet
+            .try_warning("Careful")
+            .unwrap_or_else(PrintError::into_inner)
+            .done()
+            .try_done();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn try_transitions_succeed() {
+        let io = Print::new(Vec::new())
+            .try_h2("Example Buildpack")
+            .unwrap()
+            .try_bullet("Ruby version")
+            .unwrap()
+            .try_sub_bullet("Installing")
+            .unwrap()
+            .done()
+            .try_done()
+            .unwrap();
+
+        let expected = formatdoc! {"
+
+            ## Example Buildpack
+
+            - Ruby version
+              - Installing
+            - Done (finished in < 0.1s)
+        "};
+        assert_eq!(expected, strip_ansi(String::from_utf8_lossy(&io)));
+    }
+
     #[test]
     fn test_important() {
         let writer = Vec::new();
@@ -0,0 +1,207 @@
+//! Hiding the middle of long streamed output and keeping a transcript of all of it. See
+//! [`crate::StreamOptions::collapse`] and [`crate::StreamOptions::transcript`].
+use crate::StreamOptions;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+/// The state of the streamed lines, shared between all writers of a stream.
+#[derive(Debug)]
+pub(crate) struct LineFilter(Mutex<Lines>);
+
+#[derive(Debug)]
+struct Lines {
+    collapse: Option<Collapse>,
+    transcript: Option<Transcript>,
+}
+
+#[derive(Debug)]
+struct Collapse {
+    head: usize,
+    tail: usize,
+    /// Lines seen so far, including the ones that were written
+    seen: usize,
+    /// The most recent lines after the head, at most `tail` of them
+    buffered: VecDeque<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct Transcript {
+    path: PathBuf,
+    /// `None` after a write failed
+    file: Option<BufWriter<File>>,
+}
+
+/// What's left to print when the stream is done.
+#[derive(Debug, Default)]
+pub(crate) struct Finished {
+    /// Lines that were neither written nor kept for the tail
+    pub(crate) hidden: usize,
+    pub(crate) tail: Vec<Vec<u8>>,
+    /// The path of the transcript, if all output was written to it
+    pub(crate) transcript: Option<PathBuf>,
+}
+
+impl LineFilter {
+    /// A transcript file that cannot be created is skipped, the path is then not reported.
+    pub(crate) fn new(options: &StreamOptions) -> Self {
+        let collapse = options.collapse.map(|(head, tail)| Collapse {
+            head,
+            tail,
+            seen: 0,
+            buffered: VecDeque::new(),
+        });
+        let transcript = options.transcript.as_ref().map(|path| Transcript {
+            path: path.clone(),
+            file: File::create(path).ok().map(BufWriter::new),
+        });
+        Self(Mutex::new(Lines {
+            collapse,
+            transcript,
+        }))
+    }
+
+    /// Copy a line to the transcript.
+    pub(crate) fn transcribe(&self, line: &[u8]) {
+        let mut lines = self.lock();
+        if let Some(Transcript { file, .. }) = lines.transcript.as_mut() {
+            if file
+                .as_mut()
+                .is_some_and(|file| file.write_all(line).is_err())
+            {
+                *file = None;
+            }
+        }
+    }
+
+    /// Returns the line if it should be written now, otherwise an empty line.
+    pub(crate) fn collapse(&self, line: Vec<u8>) -> Vec<u8> {
+        let mut lines = self.lock();
+        let Some(collapse) = lines.collapse.as_mut() else {
+            return line;
+        };
+        if line.is_empty() {
+            return line;
+        }
+
+        collapse.seen += 1;
+        if collapse.seen <= collapse.head {
+            return line;
+        }
+        if collapse.tail > 0 {
+            if collapse.buffered.len() == collapse.tail {
+                collapse.buffered.pop_front();
+            }
+            collapse.buffered.push_back(line);
+        }
+        Vec::new()
+    }
+
+    /// Take the lines kept for the tail and close the transcript.
+    pub(crate) fn finish(&self) -> Finished {
+        let mut lines = self.lock();
+        let (hidden, tail) = match lines.collapse.as_mut() {
+            Some(collapse) => {
+                let tail = std::mem::take(&mut collapse.buffered);
+                let hidden = collapse.seen.saturating_sub(collapse.head) - tail.len();
+                (hidden, Vec::from(tail))
+            }
+            None => (0, Vec::new()),
+        };
+        let transcript = lines.transcript.take().and_then(|transcript| {
+            let mut file = transcript.file?;
+            file.flush().ok().map(|()| transcript.path)
+        });
+        Finished {
+            hidden,
+            tail,
+            transcript,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lines> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// For example `... 4,210 lines hidden ...`.
+pub(crate) fn hidden_marker(hidden: usize) -> String {
+    let lines = if hidden == 1 { "line" } else { "lines" };
+    format!("... {} {lines} hidden ...", thousands(hidden))
+}
+
+/// Formats a number with a comma between each group of three digits.
+fn thousands(number: usize) -> String {
+    let digits = number.to_string();
+    digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        // The digits are ASCII, every chunk is valid UTF-8
+        .map(|group| String::from_utf8_lossy(group))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_thousands() {
+        assert_eq!("0", thousands(0));
+        assert_eq!("999", thousands(999));
+        assert_eq!("4,210", thousands(4_210));
+        assert_eq!("1,000,000", thousands(1_000_000));
+    }
+
+    #[test]
+    fn test_hidden_marker() {
+        assert_eq!("... 1 line hidden ...", hidden_marker(1));
+        assert_eq!("... 4,210 lines hidden ...", hidden_marker(4_210));
+    }
+
+    #[test]
+    fn collapse_keeps_head_and_tail() {
+        let filter = LineFilter::new(&StreamOptions::new().collapse(2, 2));
+        let written = (1..=7)
+            .map(|n| filter.collapse(format!("{n}\n").into_bytes()))
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(b"1\n2\n".to_vec(), written);
+
+        let finished = filter.finish();
+        assert_eq!(3, finished.hidden);
+        assert_eq!(vec![b"6\n".to_vec(), b"7\n".to_vec()], finished.tail);
+        assert_eq!(None, finished.transcript);
+    }
+
+    #[test]
+    fn collapse_short_output_hides_nothing() {
+        let filter = LineFilter::new(&StreamOptions::new().collapse(2, 2));
+        for line in ["1\n", "2\n", "3\n"] {
+            filter.collapse(line.into());
+        }
+        let finished = filter.finish();
+        assert_eq!(0, finished.hidden);
+        assert_eq!(vec![b"3\n".to_vec()], finished.tail);
+    }
+
+    #[test]
+    fn transcript_contains_all_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.log");
+        let filter = LineFilter::new(&StreamOptions::new().collapse(0, 0).transcript(&path));
+        for line in ["hello\n", "world\n"] {
+            filter.transcribe(line.as_bytes());
+            assert_eq!(Vec::<u8>::new(), filter.collapse(line.into()));
+        }
+
+        let finished = filter.finish();
+        assert_eq!(2, finished.hidden);
+        assert_eq!(Some(path.clone()), finished.transcript);
+        assert_eq!("hello\nworld\n", std::fs::read_to_string(path).unwrap());
+    }
+}
@@ -0,0 +1,264 @@
+//! Writing a formatted error when the program panics while output is in progress. See
+//! [`crate::Print::panic_guard`].
+use crate::ansi_escape::ANSI;
+use crate::util::ParagraphInspectWrite;
+use crate::write_paragraph;
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once, PoisonError};
+use std::thread::ThreadId;
+
+/// Each live guard and the thread its writer was last used on.
+static GUARDS: Mutex<Vec<(u64, ThreadId)>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// A panic on this thread, waiting to be written by a guarded writer as it's dropped.
+    static PANIC: RefCell<Option<Panic>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+struct Panic {
+    /// The message and location, written as an error paragraph
+    paragraph: String,
+    /// What the default hook would print, used when no writer printed the paragraph. `None`
+    /// when the previous hook already printed it.
+    default: Option<String>,
+}
+
+/// Marks a writer as guarded while it's alive.
+///
+/// The guard follows the writer: the hook only keeps the message of a panic on the thread the
+/// writer was last used on, since that's the only thread that drops the writer as it unwinds.
+#[derive(Debug)]
+pub(crate) struct PanicGuard<W> {
+    id: u64,
+    thread: ThreadId,
+    /// Called when the guarded writer is dropped. Stored here so that only guarded writers need
+    /// `W: Write` to be dropped.
+    on_drop: fn(&mut ParagraphInspectWrite<W>),
+}
+
+impl<W: Write> PanicGuard<W> {
+    pub(crate) fn new() -> Self {
+        install_hook();
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let thread = std::thread::current().id();
+        lock_guards().push((id, thread));
+        Self {
+            id,
+            thread,
+            on_drop: write_panic,
+        }
+    }
+}
+
+impl<W> PanicGuard<W> {
+    /// Called each time the writer prints for its [`crate::Print`], which is only done by the
+    /// thread that owns it. The writer might have been moved to another thread since.
+    pub(crate) fn used(&mut self) {
+        let current = std::thread::current().id();
+        if current != self.thread {
+            self.thread = current;
+            if let Some(guard) = lock_guards().iter_mut().find(|(id, _)| *id == self.id) {
+                guard.1 = current;
+            }
+        }
+        if !std::thread::panicking() {
+            report_unprinted();
+        }
+    }
+}
+
+impl<W> Drop for PanicGuard<W> {
+    fn drop(&mut self) {
+        let mut guards = lock_guards();
+        if let Some(index) = guards.iter().position(|(id, _)| *id == self.id) {
+            guards.swap_remove(index);
+        }
+    }
+}
+
+fn lock_guards() -> std::sync::MutexGuard<'static, Vec<(u64, ThreadId)>> {
+    GUARDS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Print a kept panic that no writer printed, for example because it was caught with
+/// `catch_unwind` before it reached the writer, so it's not lost.
+fn report_unprinted() {
+    if let Some(default) = PANIC.with(RefCell::take).and_then(|panic| panic.default) {
+        eprintln!("{default}");
+    }
+}
+
+/// Whether `RUST_BACKTRACE` asks for a backtrace, which only the previous hook can print.
+fn backtrace_enabled() -> bool {
+    std::env::var_os("RUST_BACKTRACE").is_some_and(|value| value != "0")
+}
+
+/// Chain a hook in front of the current one, once per process.
+///
+/// When a guarded writer was last used on the panicking thread, the hook keeps the message for
+/// that writer instead of calling the previous hook, unless `RUST_BACKTRACE` is set. Other
+/// threads, and programs that abort on panic, get the previous hook.
+fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            report_unprinted();
+            let current = std::thread::current();
+            let guarded =
+                !cfg!(panic = "abort") && lock_guards().iter().any(|(_, id)| *id == current.id());
+            let printed = !guarded || backtrace_enabled();
+            if printed {
+                previous(info);
+            }
+            if !guarded {
+                return;
+            }
+
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("Box<dyn Any>"));
+            let location = info
+                .location()
+                .map(|location| format!(" at {location}"))
+                .unwrap_or_default();
+            let panic = Panic {
+                paragraph: format!("Internal error: panicked{location}\n\n{message}"),
+                default: (!printed).then(|| {
+                    format!(
+                        "thread '{}' panicked{location}:\n{message}",
+                        current.name().unwrap_or("<unnamed>")
+                    )
+                }),
+            };
+            PANIC.with(|kept| kept.replace(Some(panic)));
+        }));
+    });
+}
+
+impl<W> Drop for ParagraphInspectWrite<W> {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.panic_guard.as_ref().map(|guard| guard.on_drop) {
+            on_drop(self);
+        }
+    }
+}
+
+/// Write the panic of the current thread, if any, as the guarded writer is dropped.
+fn write_panic<W: Write>(write: &mut ParagraphInspectWrite<W>) {
+    if write.inner.is_none() || !std::thread::panicking() {
+        report_unprinted();
+        return;
+    }
+    let Some(panic) = PANIC.with(RefCell::take) else {
+        return;
+    };
+
+    // Finish the current line, such as the dots of a timer, before the error
+    let result = if write.newlines_since_last_char == 0 {
+        writeln!(write)
+    } else {
+        Ok(())
+    };
+    // Drop implementations must not panic. We intentionally ignore the potential error here.
+    let _ = result.and_then(|()| write_paragraph(write, &ANSI::Red, panic.paragraph));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::LockedWriter;
+    use crate::{strip_ansi, Print};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn has_kept_panic() -> bool {
+        PANIC.with(|kept| kept.borrow().is_some())
+    }
+
+    #[test]
+    fn panic_during_timer_writes_error() {
+        let writer = LockedWriter::new(Vec::new());
+        let output = writer.clone();
+        let result = std::thread::spawn(move || {
+            let _timer = Print::new(output)
+                .panic_guard()
+                .without_header()
+                .bullet("Installing")
+                .start_timer_with_options(
+                    "Downloading",
+                    crate::TimerOptions::new().interval(Duration::from_secs(60)),
+                );
+            panic!("Download failed");
+        })
+        .join();
+        assert!(result.is_err());
+
+        let output = strip_ansi(String::from_utf8_lossy(&writer.unwrap()));
+        let (timer, error) = output
+            .split_once("! Internal error: panicked at src/panic_guard.rs:")
+            .unwrap();
+        assert_eq!("- Installing\n  - Downloading ... \n\n", timer);
+        assert!(error.ends_with("\n!\n! Download failed\n\n"), "{error}");
+    }
+
+    #[test]
+    fn without_panic_nothing_is_added() {
+        let io = Print::new(Vec::new())
+            .panic_guard()
+            .h2("Example Buildpack")
+            .bullet("Installing")
+            .done()
+            .done();
+        assert!(!String::from_utf8_lossy(&io).contains('!'));
+    }
+
+    #[test]
+    fn caught_panic_is_not_kept() {
+        let writer = LockedWriter::new(Vec::new());
+        let output = writer.clone();
+        std::thread::spawn(move || {
+            let bullet = Print::new(output)
+                .panic_guard()
+                .without_header()
+                .bullet("Installing");
+            assert!(std::panic::catch_unwind(|| panic!("Caught")).is_err());
+
+            bullet.sub_bullet("Downloading").done().done();
+            assert!(!has_kept_panic());
+        })
+        .join()
+        .unwrap();
+
+        assert!(!String::from_utf8_lossy(&writer.unwrap()).contains("Caught"));
+    }
+
+    #[test]
+    fn guard_follows_the_writer_to_another_thread() {
+        let output = Print::new(Vec::new()).panic_guard().without_header();
+        let (used, wait_for_use) = mpsc::channel();
+        let (checked, wait_for_check) = mpsc::channel::<()>();
+        let task = std::thread::spawn(move || {
+            let bullet = output.bullet("Installing");
+            used.send(()).unwrap();
+            wait_for_check.recv().unwrap();
+            bullet.done().done()
+        });
+
+        wait_for_use.recv().unwrap();
+        assert!(std::panic::catch_unwind(|| panic!("Not guarded")).is_err());
+        assert!(!has_kept_panic());
+        checked.send(()).unwrap();
+
+        let io = task.join().unwrap();
+        assert!(!String::from_utf8_lossy(&io).contains("Not guarded"));
+    }
+}
@@ -0,0 +1,391 @@
+//! Printing from parallel tasks without interleaving their output.
+use crate::print_error::PrintError;
+use crate::util::ParagraphInspectWrite;
+use crate::{state, Clock, Print};
+use std::io::Write;
+use std::sync::mpsc;
+
+/// Controls when the output of a parallel task is written, see [`Print::parallel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushOrder {
+    /// Write the output of the tasks in the order they were created.
+    ///
+    /// The output of the first unfinished task is written as it happens, the output of the other
+    /// tasks is held back until all tasks before them have finished.
+    #[default]
+    InOrder,
+    /// Write the output of each task in a single block as soon as it finishes.
+    AsCompleted,
+}
+
+/// The writer of a task printer created by [`Print::parallel`].
+///
+/// Sends the output to the parent printer. The task is finished when this writer is dropped,
+/// usually by calling [`Print::finish`] or by dropping the task printer.
+#[derive(Debug)]
+pub struct ParallelWrite {
+    index: usize,
+    sender: mpsc::Sender<(usize, Message)>,
+}
+
+#[derive(Debug)]
+enum Message {
+    Write(Vec<u8>),
+    Finish,
+    /// Sent once the closure passed to [`Print::parallel`] returns or panics.
+    Close,
+}
+
+/// Stops the receiver when dropped, so it also stops when the closure passed to
+/// [`Print::parallel`] panics or a task printer was leaked. Otherwise the scope would wait for
+/// the receiver forever.
+struct CloseOnDrop(mpsc::Sender<(usize, Message)>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.send((0, Message::Close));
+    }
+}
+
+impl Write for ParallelWrite {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sender
+            .send((self.index, Message::Write(buf.to_vec())))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ParallelWrite {
+    fn drop(&mut self) {
+        // The parent already stopped receiving if the channel is closed
+        let _ = self.sender.send((self.index, Message::Finish));
+    }
+}
+
+impl<W, C> Print<state::Bullet<W>, C>
+where
+    W: Write + Send + Sync + 'static,
+    C: Clock,
+{
+    /// Print from `count` tasks running in parallel, such as concurrent downloads.
+    ///
+    /// The closure receives one printer per task. Each task writes its own bullet sections
+    /// and the output of a task is never interleaved with the output of another task. The
+    /// [`FlushOrder`] controls when the output is written.
+    ///
+    /// A task is finished when its printer is dropped or [`Print::finish`] is called. Every task
+    /// printer must be finished before the closure returns, output written by a task after that
+    /// is an error. Any output that is still held back is written when the closure returns.
+    ///
+    /// ```rust
+    /// use bullet_stream::{FlushOrder, Print};
+    ///
+    /// let mut output = Print::new(std::io::stdout()).h2("Example Buildpack");
+    ///
+    /// output.parallel(2, FlushOrder::InOrder, |tasks| {
+    ///     std::thread::scope(|scope| {
+    ///         for (task, name) in tasks.into_iter().zip(["Ruby", "Node.js"]) {
+    ///             scope.spawn(move || {
+    ///                 task.bullet(format!("Downloading {name}"))
+    ///                     .sub_bullet("Verifying checksum")
+    ///                     .done()
+    ///                     .finish();
+    ///             });
+    ///         }
+    ///     });
+    /// });
+    ///
+    /// output.done();
+    /// ```
+    #[allow(clippy::missing_panics_doc)]
+    pub fn parallel<F, T>(&mut self, count: usize, order: FlushOrder, f: F) -> T
+    where
+        F: FnOnce(Vec<Print<state::Bullet<ParallelWrite>, C>>) -> T,
+    {
+        self.try_parallel(count, order, f)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::parallel`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    ///
+    /// The closure always runs to completion, even when the output cannot be written. Its
+    /// output is available from the error via [`PrintError::into_inner`].
+    pub fn try_parallel<F, T>(
+        &mut self,
+        count: usize,
+        order: FlushOrder,
+        f: F,
+    ) -> Result<T, PrintError<T>>
+    where
+        F: FnOnce(Vec<Print<state::Bullet<ParallelWrite>, C>>) -> T,
+    {
+        let (sender, receiver) = mpsc::channel::<(usize, Message)>();
+        let tasks = (0..count)
+            .map(|index| Print {
+                started: None,
+                events: self.events.clone(),
+                secrets: self.secrets.clone(),
+                warnings: self.warnings.clone(),
+                sections: self.sections.for_task(),
+                clock: self.clock.clone(),
+                state: state::Bullet {
+                    write: ParagraphInspectWrite {
+                        inner: Some(ParallelWrite {
+                            index,
+                            sender: sender.clone(),
+                        }),
+                        was_paragraph: self.state.write.was_paragraph,
+                        newlines_since_last_char: self.state.write.newlines_since_last_char,
+                        color: self.state.write.color,
+                        secrets: self.secrets.clone(),
+                        panic_guard: None,
+                    },
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        let write_result = &mut result;
+        let write = &mut self.state.write;
+        let value = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut blocks = Blocks::new(count, order);
+                for (index, message) in receiver {
+                    let flush = match message {
+                        Message::Write(bytes) => blocks.write(index, bytes),
+                        Message::Finish => blocks.finish(index),
+                        Message::Close => break,
+                    };
+                    for bytes in flush {
+                        // After a write error, keep receiving so the tasks never observe a
+                        // closed channel.
+                        if write_result.is_ok() {
+                            *write_result = write.write_all(&bytes).and_then(|()| write.flush());
+                        }
+                    }
+                }
+                for bytes in blocks.remaining() {
+                    if write_result.is_ok() {
+                        *write_result = write.write_all(&bytes).and_then(|()| write.flush());
+                    }
+                }
+            });
+
+            let _close = CloseOnDrop(sender);
+            f(tasks)
+        });
+
+        PrintError::from_io(value, result)
+    }
+}
+
+impl<C> Print<state::Bullet<ParallelWrite>, C> {
+    /// Finish a task created by [`Print::parallel`]. This is the same as dropping it.
+    pub fn finish(self) {
+        drop(self);
+    }
+}
+
+/// Holds back the output of parallel tasks until it's time to write it.
+#[derive(Debug)]
+struct Blocks {
+    order: FlushOrder,
+    buffers: Vec<Vec<u8>>,
+    finished: Vec<bool>,
+    /// The first task that has not finished, only used for [`FlushOrder::InOrder`].
+    current: usize,
+}
+
+impl Blocks {
+    fn new(count: usize, order: FlushOrder) -> Self {
+        Self {
+            order,
+            buffers: vec![Vec::new(); count],
+            finished: vec![false; count],
+            current: 0,
+        }
+    }
+
+    /// Record output from a task, returns what can be written now.
+    fn write(&mut self, index: usize, bytes: Vec<u8>) -> Vec<Vec<u8>> {
+        if self.order == FlushOrder::InOrder && index == self.current {
+            vec![bytes]
+        } else {
+            self.buffers[index].extend(bytes);
+            Vec::new()
+        }
+    }
+
+    /// Record that a task finished, returns what can be written now.
+    fn finish(&mut self, index: usize) -> Vec<Vec<u8>> {
+        self.finished[index] = true;
+        match self.order {
+            FlushOrder::AsCompleted => vec![std::mem::take(&mut self.buffers[index])],
+            FlushOrder::InOrder => {
+                let mut flush = Vec::new();
+                while self.finished.get(self.current) == Some(&true) {
+                    self.current += 1;
+                    if let Some(buffer) = self.buffers.get_mut(self.current) {
+                        flush.push(std::mem::take(buffer));
+                    }
+                }
+                flush
+            }
+        }
+    }
+
+    /// Everything that was held back, in task order.
+    fn remaining(&mut self) -> Vec<Vec<u8>> {
+        self.buffers.iter_mut().map(std::mem::take).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_blocks_in_order() {
+        let mut blocks = Blocks::new(3, FlushOrder::InOrder);
+        assert_eq!(vec![b"a1".to_vec()], blocks.write(0, b"a1".to_vec()));
+        assert!(blocks.write(2, b"c1".to_vec()).is_empty());
+        assert!(blocks.write(1, b"b1".to_vec()).is_empty());
+        assert!(blocks.finish(1).is_empty());
+        assert_eq!(vec![b"b1".to_vec(), b"c1".to_vec()], blocks.finish(0));
+        assert_eq!(vec![b"c2".to_vec()], blocks.write(2, b"c2".to_vec()));
+        assert!(blocks.finish(2).is_empty());
+        assert!(blocks.remaining().iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn test_blocks_as_completed() {
+        let mut blocks = Blocks::new(2, FlushOrder::AsCompleted);
+        assert!(blocks.write(0, b"a1".to_vec()).is_empty());
+        assert!(blocks.write(1, b"b1".to_vec()).is_empty());
+        assert!(blocks.write(0, b"a2".to_vec()).is_empty());
+        assert_eq!(vec![b"b1".to_vec()], blocks.finish(1));
+        assert_eq!(vec![b"a1a2".to_vec(), Vec::new()], blocks.remaining());
+    }
+
+    #[test]
+    fn parallel_tasks_do_not_interleave() {
+        for (order, expected) in [
+            (
+                FlushOrder::InOrder,
+                formatdoc! {"
+                    - Downloading ruby
+                      - Verifying checksum
+                    - Downloading node
+                      - Verifying checksum
+                    - Done (finished in < 0.1s)
+                "},
+            ),
+            (
+                FlushOrder::AsCompleted,
+                formatdoc! {"
+                    - Downloading node
+                      - Verifying checksum
+                    - Downloading ruby
+                      - Verifying checksum
+                    - Done (finished in < 0.1s)
+                "},
+            ),
+        ] {
+            let capture = Capture::new();
+            let clock = ManualClock::new();
+            let mut output = print(&capture, &clock).without_header();
+
+            // Force the tasks to interleave: ruby starts first, node finishes first.
+            let barrier = Arc::new(Barrier::new(2));
+            let value = output.parallel(2, order, |tasks| {
+                std::thread::scope(|scope| {
+                    for (task, name) in tasks.into_iter().zip(["ruby", "node"]) {
+                        let barrier = Arc::clone(&barrier);
+                        scope.spawn(move || {
+                            if name == "ruby" {
+                                let task = task.bullet("Downloading ruby");
+                                barrier.wait();
+                                barrier.wait();
+                                drop(task.sub_bullet("Verifying checksum").done());
+                            } else {
+                                barrier.wait();
+                                task.bullet("Downloading node")
+                                    .sub_bullet("Verifying checksum")
+                                    .done()
+                                    .finish();
+                                barrier.wait();
+                            }
+                        });
+                    }
+                });
+                42
+            });
+            assert_eq!(42, value);
+            output.done();
+
+            assert_eq!(expected, capture.contents());
+        }
+    }
+
+    #[test]
+    fn panic_in_parallel_is_propagated() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+        let mut output = print(&capture, &clock).without_header();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            output.parallel(2, FlushOrder::InOrder, |tasks| {
+                std::thread::scope(|scope| {
+                    for (index, task) in tasks.into_iter().enumerate() {
+                        scope.spawn(move || {
+                            let task = task.bullet(format!("Task {index}"));
+                            assert!(index == 0, "Task {index} failed");
+                            task.done().finish();
+                        });
+                    }
+                });
+            });
+        }));
+        assert!(result.is_err());
+        output.done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Task 0
+                - Task 1
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn leaked_task_does_not_deadlock() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+        let mut output = print(&capture, &clock).without_header();
+
+        let leaked = output.parallel(1, FlushOrder::InOrder, |mut tasks| {
+            tasks.pop().unwrap().bullet("Leaked").done()
+        });
+        assert!(leaked.try_bullet("Too late").is_err());
+        output.done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Leaked
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+    }
+}
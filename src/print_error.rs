@@ -0,0 +1,75 @@
+use std::fmt::{Debug, Display, Formatter};
+
+/// The result of a fallible [`crate::Print`] transition into the state `S`.
+pub type PrintResult<S, C = crate::SystemClock> =
+    Result<crate::Print<S, C>, PrintError<crate::Print<S, C>>>;
+
+/// An error writing to the output, returned by the `try_*` variants of [`crate::Print`]
+/// methods such as [`crate::Print::try_bullet`].
+///
+/// The error carries the value that the infallible method would have returned, so the
+/// caller can recover it with [`PrintError::into_inner`] and decide how to continue. For
+/// example, a buildpack may want to keep running when the user pipes the output to
+/// `head` and the pipe is closed:
+///
+/// ```rust
+/// use bullet_stream::Print;
+///
+/// let output = Print::new(std::io::stdout()).h2("Example Buildpack");
+/// let output = output
+///     .try_bullet("Ruby version")
+///     .unwrap_or_else(|error| error.into_inner());
+/// ```
+pub struct PrintError<T> {
+    value: T,
+    source: std::io::Error,
+}
+
+impl<T> PrintError<T> {
+    pub(crate) fn new(value: T, source: std::io::Error) -> Self {
+        Self { value, source }
+    }
+
+    /// Converts an IO result into a result carrying the given value on both sides.
+    pub(crate) fn from_io(value: T, result: std::io::Result<()>) -> Result<T, Self> {
+        match result {
+            Ok(()) => Ok(value),
+            Err(source) => Err(Self::new(value, source)),
+        }
+    }
+
+    /// Returns the value that the infallible method would have returned.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the value that the infallible method would have returned and the IO error.
+    pub fn into_parts(self) -> (T, std::io::Error) {
+        (self.value, self.source)
+    }
+
+    /// The underlying IO error.
+    pub fn io_error(&self) -> &std::io::Error {
+        &self.source
+    }
+}
+
+impl<T> Debug for PrintError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrintError")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for PrintError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Output error: {}", self.source)
+    }
+}
+
+impl<T> std::error::Error for PrintError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
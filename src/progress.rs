@@ -0,0 +1,401 @@
+//! Reporting the progress of a download, or any other transfer of bytes.
+use crate::ansi_escape::{self, ANSI};
+use crate::background_printer::{self, Driver};
+use crate::event::EventKind;
+use crate::print_error::{PrintError, PrintResult};
+use crate::{
+    byte_format, duration_format, state, strip_ansi, style, writeln_now, Clock, Print,
+    SectionParent,
+};
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the byte count is checked for new milestones.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The distance between milestones when the total size is unknown.
+const UNKNOWN_TOTAL_STEP: u64 = 5_000_000;
+
+/// The number of bytes transferred so far, shared between the wrappers and the printer.
+#[derive(Debug)]
+pub(crate) struct ByteCount {
+    bytes: AtomicU64,
+    milestones: Mutex<Milestones>,
+}
+
+impl ByteCount {
+    fn add(&self, bytes: usize) {
+        self.bytes
+            .fetch_add(u64::try_from(bytes).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The markers of the milestones passed since the last call, such as `10%... 20%... `.
+    fn passed_milestones(&self) -> String {
+        self.milestones
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .passed(self.get())
+    }
+}
+
+/// Tracks which milestones were already printed.
+#[derive(Debug)]
+struct Milestones {
+    total: Option<u64>,
+    /// The number of milestones printed so far.
+    printed: u64,
+}
+
+impl Milestones {
+    /// One milestone for every 10% of the total, or every 5 MB when the total is unknown.
+    fn passed(&mut self, bytes: u64) -> String {
+        let mut markers = String::new();
+        match self.total {
+            Some(0) => {}
+            Some(total) => {
+                let percent = u128::from(bytes.min(total)) * 100 / u128::from(total);
+                while u128::from(self.printed + 1) * 10 <= percent {
+                    self.printed += 1;
+                    markers.push_str(&format!("{}%... ", self.printed * 10));
+                }
+            }
+            None => {
+                while (self.printed + 1) * UNKNOWN_TOTAL_STEP <= bytes {
+                    self.printed += 1;
+                    let marker = byte_format::human(self.printed * UNKNOWN_TOTAL_STEP);
+                    markers.push_str(&format!("{marker}... "));
+                }
+            }
+        }
+        markers
+    }
+}
+
+/// A [`Read`] that reports the bytes read to a progress printer, see [`Print::reader`].
+#[derive(Debug)]
+pub struct ProgressRead<R> {
+    inner: R,
+    count: Arc<ByteCount>,
+}
+
+impl<R> ProgressRead<R> {
+    /// Unwrap the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ProgressRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.add(read);
+        Ok(read)
+    }
+}
+
+/// A [`Write`] that reports the bytes written to a progress printer, see [`Print::writer`].
+#[derive(Debug)]
+pub struct ProgressWrite<T> {
+    inner: T,
+    count: Arc<ByteCount>,
+}
+
+impl<T> ProgressWrite<T> {
+    /// Unwrap the underlying writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Write> Write for ProgressWrite<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count.add(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W, P, C> Print<state::SubBullet<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    /// Report the progress of a download, or any other transfer of bytes.
+    ///
+    /// Like [`Print::start_timer`], but instead of dots a marker is printed for each milestone
+    /// that is passed. When the total size is known there is a milestone for every 10% (`10%...
+    /// 20%...`), otherwise there is one every 5 MB (`5 MB... 10 MB...`). Count the bytes by
+    /// wrapping a reader or writer with [`Print::reader`] or [`Print::writer`].
+    ///
+    /// ```rust
+    /// use bullet_stream::Print;
+    ///
+    /// let body = vec![0_u8; 2_000];
+    /// let mut download = Print::new(std::io::stdout())
+    ///     .h2("Example Buildpack")
+    ///     .bullet("Ruby version")
+    ///     .start_progress("Downloading", Some(2_000));
+    ///
+    /// let mut file = Vec::new();
+    /// std::io::copy(&mut download.reader(body.as_slice()), &mut file).unwrap();
+    ///
+    /// download.done().done().done();
+    /// ```
+    ///
+    /// This function will transition your buildpack output to [`state::Progress`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn start_progress(
+        self,
+        s: impl AsRef<str>,
+        total_bytes: Option<u64>,
+    ) -> Print<state::Progress<W, P>, C> {
+        self.try_start_progress(s, total_bytes)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_progress`], but returns a [`PrintError`] instead of panicking when
+    /// the output cannot be written.
+    // The error carries the running download so the caller can keep using it, like
+    // `try_start_stream`.
+    #[allow(clippy::result_large_err)]
+    pub fn try_start_progress(
+        mut self,
+        s: impl AsRef<str>,
+        total_bytes: Option<u64>,
+    ) -> PrintResult<state::Progress<W, P>, C> {
+        self.emit(EventKind::ProgressStart, s.as_ref(), Self::DEPTH, None);
+        // Do not emit a newline after the message
+        let line = self.state.write.secrets.redact(Self::style(s));
+        let line = if self.state.write.color {
+            line
+        } else {
+            strip_ansi(line)
+        };
+        let result = write!(self.state.write, "{line}").and_then(|()| self.state.write.flush());
+
+        let count = Arc::new(ByteCount {
+            bytes: AtomicU64::new(0),
+            milestones: Mutex::new(Milestones {
+                total: total_bytes,
+                printed: 0,
+            }),
+        });
+        let color = self.state.write.color;
+        let milestones = {
+            let count = Arc::clone(&count);
+            move |_| dim(color, count.passed_milestones())
+        };
+
+        let started = self.clock.now();
+        let clock = self.clock.clone();
+        let output = self.map_state(|state| state::Progress {
+            started,
+            count,
+            write: background_printer::print_interval(
+                state.write,
+                clock,
+                background_printer::every(POLL_INTERVAL),
+                String::from(" "),
+                milestones,
+                String::new(),
+                Driver::Thread,
+            ),
+            parent: PhantomData,
+        });
+        PrintError::from_io(output, result)
+    }
+}
+
+impl<W, P, C> Print<state::Progress<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    const DEPTH: usize = <state::SubBullet<W, P> as crate::private::Sealed<W>>::DEPTH;
+
+    /// Wrap a reader, such as the body of an HTTP response, to count the bytes read from it.
+    pub fn reader<R: Read>(&self, reader: R) -> ProgressRead<R> {
+        ProgressRead {
+            inner: reader,
+            count: Arc::clone(&self.state.count),
+        }
+    }
+
+    /// Wrap a writer, such as a file, to count the bytes written to it.
+    pub fn writer<T: Write>(&self, writer: T) -> ProgressWrite<T> {
+        ProgressWrite {
+            inner: writer,
+            count: Arc::clone(&self.state.count),
+        }
+    }
+
+    /// Count bytes that were transferred without a wrapper, for example from an async stream.
+    pub fn add_bytes(&self, bytes: usize) {
+        self.state.count.add(bytes);
+    }
+
+    /// Interrupt the progress with a message explaining why.
+    ///
+    /// The number of bytes transferred so far is not printed.
+    pub fn cancel(self, why_details: impl AsRef<str>) -> Print<state::SubBullet<W, P>, C> {
+        self.try_cancel(why_details)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::cancel`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_cancel(
+        self,
+        why_details: impl AsRef<str>,
+    ) -> PrintResult<state::SubBullet<W, P>, C> {
+        self.emit(
+            EventKind::ProgressCancel,
+            why_details.as_ref(),
+            Self::DEPTH,
+            Some(self.clock.elapsed(self.state.started)),
+        );
+        self.finish(style::details(why_details))
+    }
+
+    /// Finalize the progress output with the size, speed and duration of the transfer.
+    ///
+    /// Any milestones that were passed but not printed yet are printed first. Transitions back
+    /// to a [`state::SubBullet`].
+    #[must_use]
+    pub fn done(self) -> Print<state::SubBullet<W, P>, C> {
+        self.try_done().expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::done`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_done(self) -> PrintResult<state::SubBullet<W, P>, C> {
+        let duration = self.clock.elapsed(self.state.started);
+        self.emit(EventKind::ProgressDone, "Done", Self::DEPTH, Some(duration));
+
+        let bytes = self.state.count.get();
+        let mut details = vec![byte_format::human(bytes)];
+        if !duration.is_zero() {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let speed = (u128::from(bytes) * 1000 / duration.as_millis().max(1)) as u64;
+            details.push(format!("{}/s", byte_format::human(speed)));
+        }
+        details.push(duration_format::human(&duration));
+
+        self.finish(style::details(details.join(", ")))
+    }
+
+    /// Stop the background printer, print any remaining milestones, then the details.
+    fn finish(self, details: String) -> PrintResult<state::SubBullet<W, P>, C> {
+        let mut result = Ok(());
+        let output = self.map_state(|state| {
+            let (mut io, progress_result) = match state.write.stop() {
+                Ok(stopped) => stopped,
+                // Stdlib docs recommend using `resume_unwind` to resume the thread panic
+                // <https://doc.rust-lang.org/std/thread/type.Result.html>
+                Err(e) => std::panic::resume_unwind(e),
+            };
+
+            let milestones = dim(io.color, state.count.passed_milestones());
+            result = progress_result
+                .and_then(|()| write!(io, "{milestones}"))
+                .and_then(|()| writeln_now(&mut io, details));
+            state::SubBullet {
+                write: io,
+                parent: PhantomData,
+            }
+        });
+
+        PrintError::from_io(output, result)
+    }
+}
+
+fn dim(color: bool, markers: String) -> String {
+    if color && !markers.is_empty() {
+        ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, markers)
+    } else {
+        markers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_milestones() {
+        let mut milestones = Milestones {
+            total: Some(1_000),
+            printed: 0,
+        };
+        assert_eq!("", milestones.passed(99));
+        assert_eq!("10%... 20%... ", milestones.passed(250));
+        assert_eq!("", milestones.passed(250));
+        assert_eq!(
+            "30%... 40%... 50%... 60%... 70%... 80%... 90%... 100%... ",
+            milestones.passed(2_000)
+        );
+
+        let mut milestones = Milestones {
+            total: None,
+            printed: 0,
+        };
+        assert_eq!("", milestones.passed(4_999_999));
+        assert_eq!("5 MB... 10 MB... ", milestones.passed(12_000_000));
+
+        let mut milestones = Milestones {
+            total: Some(0),
+            printed: 0,
+        };
+        assert_eq!("", milestones.passed(100));
+    }
+
+    #[test]
+    fn progress_prints_milestones_and_speed() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let download = print(&capture, &clock)
+            .without_header()
+            .bullet("Ruby version")
+            .start_progress("Downloading", Some(4_000_000));
+        let mut file = download.writer(Vec::new());
+        file.write_all(&[0; 1_000_000]).unwrap();
+        clock.advance(Duration::from_secs(1));
+        let mut body = download.reader(&[0_u8; 3_000_000][..]);
+        std::io::copy(&mut body, &mut Vec::new()).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(1_000_000, file.into_inner().len());
+        let output = download.done();
+
+        let download = output.start_progress("Downloading", None);
+        download.add_bytes(6_000_000);
+        let output = download.cancel("Interrupted");
+        output.done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Ruby version
+                  - Downloading 10%... 20%... 30%... 40%... 50%... 60%... 70%... 80%... 90%... 100%... (4 MB, 2 MB/s, 2.0s)
+                  - Downloading 5 MB... (Interrupted)
+                - Done (finished in 2.0s)
+            "},
+            capture.contents()
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, RwLock};
+
+/// Replaces a secret value in the output.
+pub(crate) const MASK: &str = "[REDACTED]";
+
+/// Secret values that must never be written to the output.
+///
+/// The values are shared, so a clone sees secrets registered after it was created. This
+/// allows writers that are already running on another thread (such as a stream) to pick up
+/// newly registered secrets.
+#[derive(Clone, Default)]
+pub(crate) struct Secrets {
+    values: Arc<RwLock<Vec<String>>>,
+}
+
+impl Debug for Secrets {
+    // Never print the secret values
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let count = self.values.read().map_or(0, |values| values.len());
+        f.debug_struct("Secrets").field("count", &count).finish()
+    }
+}
+
+impl Secrets {
+    /// Registers a value to redact. Empty values are ignored.
+    pub(crate) fn add(&self, secret: impl Into<String>) {
+        let secret = secret.into();
+        if secret.is_empty() {
+            return;
+        }
+        let mut values = self
+            .values
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if !values.contains(&secret) {
+            values.push(secret);
+            // Prefer the longest match when one secret contains another
+            values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        }
+    }
+
+    /// Replaces every occurrence of a secret in the contents with [`MASK`].
+    pub(crate) fn redact(&self, contents: impl AsRef<str>) -> String {
+        let contents = contents.as_ref();
+        let redacted = self.redact_bytes(contents.as_bytes().to_vec());
+        // Secrets are valid UTF-8 and are replaced with ASCII, so the result is valid UTF-8
+        String::from_utf8(redacted).unwrap_or_else(|_| contents.to_string())
+    }
+
+    /// Replaces every occurrence of a secret in the bytes with [`MASK`].
+    pub(crate) fn redact_bytes(&self, bytes: Vec<u8>) -> Vec<u8> {
+        let values = self
+            .values
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if values.is_empty() {
+            return bytes;
+        }
+
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut index = 0;
+        while index < bytes.len() {
+            let rest = &bytes[index..];
+            if let Some(secret) = values
+                .iter()
+                .find(|secret| rest.starts_with(secret.as_bytes()))
+            {
+                result.extend_from_slice(MASK.as_bytes());
+                index += secret.len();
+            } else {
+                result.push(bytes[index]);
+                index += 1;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = Secrets::default();
+        assert_eq!("token abc123", secrets.redact("token abc123"));
+
+        secrets.add("abc123");
+        secrets.add("");
+        assert_eq!("token [REDACTED]", secrets.redact("token abc123"));
+        assert_eq!("[REDACTED][REDACTED]", secrets.redact("abc123abc123"));
+
+        // The longest secret wins
+        secrets.add("abc123xyz");
+        assert_eq!("[REDACTED]!", secrets.redact("abc123xyz!"));
+
+        // The mask is never redacted again
+        secrets.add("RED");
+        assert_eq!("[REDACTED] [REDACTED]", secrets.redact("abc123 RED"));
+    }
+
+    #[test]
+    fn clones_share_secrets() {
+        let secrets = Secrets::default();
+        let clone = secrets.clone();
+        secrets.add("hunter2");
+
+        assert_eq!(
+            b"pw=[REDACTED]\n".to_vec(),
+            clone.redact_bytes(b"pw=hunter2\n".to_vec())
+        );
+        assert_eq!("Secrets { count: 1 }", format!("{clone:?}"));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::ansi_escape::sanitize_line;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Configures how streamed output is processed before it is written.
+///
+/// By default streamed output is passed through verbatim (apart from indentation). Pass options
+/// to [`crate::Print::start_stream_with_options`] or [`crate::Print::stream_with_options`] to
+/// change that behavior:
+///
+/// ```rust
+/// use bullet_stream::{Print, StreamOptions};
+/// use std::io::Write;
+///
+/// let mut stream = Print::new(std::io::stdout())
+///     .h2("Example Buildpack")
+///     .bullet("Downloading")
+///     .start_stream_with_options(
+///         "Running `curl`",
+///         StreamOptions::new().collapse_carriage_returns(true),
+///     );
+///
+/// write!(&mut stream, "10%\r50%\r100%\n").unwrap();
+///
+/// stream.done().done().done();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    pub(crate) collapse_carriage_returns: bool,
+    pub(crate) sanitize: bool,
+    pub(crate) heartbeat: Option<Duration>,
+    pub(crate) collapse: Option<(usize, usize)>,
+    pub(crate) transcript: Option<PathBuf>,
+    pub(crate) max_captured_lines: Option<usize>,
+    pub(crate) stderr_prefix: Option<String>,
+}
+
+impl StreamOptions {
+    /// Options that pass streamed output through verbatim.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keep the final frame of lines that are redrawn with a carriage return (`\r`).
+    ///
+    /// Tools such as `curl`, `pip` and `npm` draw progress bars by writing `\r` and then
+    /// overwriting the current line. When the output is not a terminal (for example, when
+    /// it's stored in a log) every frame ends up on a single line. With this option enabled,
+    /// each line is reduced to the text after its last `\r`, so the output stays append-only.
+    /// A `\r\n` line ending is treated as `\n`.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn collapse_carriage_returns(mut self, value: bool) -> Self {
+        self.collapse_carriage_returns = value;
+        self
+    }
+
+    /// Remove untrusted escape sequences from the streamed output.
+    ///
+    /// A command's output can move the cursor, clear the screen, or set the terminal title, which
+    /// breaks the append-only guarantee of the output. It can also leave a color enabled that
+    /// bleeds into the next line, including the `remote:` prefix added by `git push`.
+    ///
+    /// With this option enabled, colors and text styles (SGR escape sequences) are preserved but
+    /// every other escape sequence and control character (except tab and carriage return) is
+    /// removed, and lines containing a style are reset at the end of the line.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn sanitize(mut self, value: bool) -> Self {
+        self.sanitize = value;
+        self
+    }
+
+    /// Print a line such as `(still running, 2m elapsed)` when the command produces no output
+    /// for the given period.
+    ///
+    /// Commands that are silent for a long time, such as a compiler working on a large file,
+    /// can look like they hung. The line is printed once per quiet period for as long as the
+    /// command stays silent, then streaming continues as usual. It's never printed in the
+    /// middle of a line of output.
+    ///
+    /// Defaults to no heartbeat.
+    #[must_use]
+    pub fn heartbeat(mut self, quiet_period: Duration) -> Self {
+        self.heartbeat = Some(quiet_period);
+        self
+    }
+
+    /// Only show the first `head` and the last `tail` lines of the output.
+    ///
+    /// Commands such as `bundle install` can print thousands of lines. With this option the
+    /// lines in between are replaced by a single line such as `... 4,210 lines hidden ...`.
+    /// The last lines are held back until the stream is done. Combine it with
+    /// [`StreamOptions::transcript`] to keep the full output.
+    ///
+    /// Defaults to showing all lines.
+    #[must_use]
+    pub fn collapse(mut self, head: usize, tail: usize) -> Self {
+        self.collapse = Some((head, tail));
+        self
+    }
+
+    /// Write the full output to a file and print its path in the `Done` line, for example
+    /// `- Done (2.3s, full output in /tmp/bundle.log)`.
+    ///
+    /// The file contains the output after the other options are applied and secrets are
+    /// redacted, without indentation. Any existing file is overwritten. If the file cannot be
+    /// written, the path is left out of the `Done` line.
+    ///
+    /// Defaults to no transcript.
+    #[must_use]
+    pub fn transcript(mut self, path: impl Into<PathBuf>) -> Self {
+        self.transcript = Some(path.into());
+        self
+    }
+
+    /// Start each line written to the stderr writer of [`crate::Print::stream_with`] with a
+    /// dim `prefix`, such as `stderr: `.
+    ///
+    /// Tools often print warnings to stderr. Marking those lines makes them stand out from the
+    /// normal output. Lines written via [`crate::Print::start_stream`] are not marked.
+    ///
+    /// To use another color, pass a prefix that is already styled, for example
+    /// `style::important("stderr: ")`. Its colors are kept as is, or removed when colors are
+    /// disabled.
+    ///
+    /// Defaults to no prefix.
+    #[must_use]
+    pub fn mark_stderr(mut self, prefix: impl Into<String>) -> Self {
+        self.stderr_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Only keep the last `lines` lines of stdout and of stderr in the output returned by
+    /// [`crate::Print::stream_with_capture`].
+    ///
+    /// Defaults to keeping all output.
+    #[must_use]
+    pub fn max_captured_lines(mut self, lines: usize) -> Self {
+        self.max_captured_lines = Some(lines);
+        self
+    }
+
+    /// Apply all enabled transformations to a single line of output.
+    ///
+    /// The line may or may not end with a newline.
+    pub(crate) fn map_line(&self, mut line: Vec<u8>) -> Vec<u8> {
+        if self.collapse_carriage_returns {
+            line = last_frame(line);
+        }
+        if self.sanitize {
+            line = sanitize_line(&line);
+        }
+        line
+    }
+}
+
+/// Returns the contents of the line after the last carriage return.
+///
+/// A trailing newline (including a `\r\n` line ending) is preserved.
+fn last_frame(mut line: Vec<u8>) -> Vec<u8> {
+    let newline = line.last() == Some(&b'\n');
+    if newline {
+        line.pop();
+    }
+    while line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    let mut frame = match line.iter().rposition(|&byte| byte == b'\r') {
+        Some(index) => line.split_off(index + 1),
+        None => line,
+    };
+    if newline {
+        frame.push(b'\n');
+    }
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_last_frame() {
+        assert_eq!(b"100%\n".to_vec(), last_frame(b"10%\r50%\r100%\n".to_vec()));
+        assert_eq!(b"100%\n".to_vec(), last_frame(b"10%\r100%\r\n".to_vec()));
+        assert_eq!(b"done\n".to_vec(), last_frame(b"done\r\n".to_vec()));
+        assert_eq!(
+            b"no newline".to_vec(),
+            last_frame(b"frame\rno newline".to_vec())
+        );
+        assert_eq!(b"\n".to_vec(), last_frame(b"\n".to_vec()));
+        assert_eq!(b"".to_vec(), last_frame(b"".to_vec()));
+    }
+
+    #[test]
+    fn map_line_defaults_to_verbatim() {
+        let line = b"10%\r100%\n".to_vec();
+        assert_eq!(line.clone(), StreamOptions::new().map_line(line));
+    }
+}
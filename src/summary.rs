@@ -0,0 +1,223 @@
+//! Repeating warnings and listing how long each section took at the end of the build, see
+//! [`crate::Print::done_with_summary`].
+use crate::ansi_escape::{strip_ansi, ANSI};
+use crate::print_error::PrintError;
+use crate::{duration_format, state, write_paragraph, writeln_now, Clock, Print, WarningCounts};
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How long each top level bullet took.
+///
+/// The finished sections are shared, so the sections of [`Print::parallel`] tasks are listed
+/// in the summary of the printer that created them. The open section belongs to one printer.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SectionTimes {
+    finished: Arc<Mutex<Vec<(String, Duration)>>>,
+    open: Option<Box<(String, Instant)>>,
+}
+
+impl SectionTimes {
+    pub(crate) fn start(&mut self, name: impl AsRef<str>, now: Instant) {
+        self.open = Some(Box::new((name.as_ref().trim().to_string(), now)));
+    }
+
+    pub(crate) fn finish(&mut self, clock: &impl Clock) {
+        if let Some(open) = self.open.take() {
+            let (name, started) = *open;
+            self.lock().push((name, clock.elapsed(started)));
+        }
+    }
+
+    /// A copy that shares the finished sections but has no open section.
+    pub(crate) fn for_task(&self) -> Self {
+        Self {
+            finished: Arc::clone(&self.finished),
+            open: None,
+        }
+    }
+
+    fn finished(&self) -> Vec<(String, Duration)> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(String, Duration)>> {
+        self.finished.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W, C> Print<state::Bullet<W>, C>
+where
+    W: Write + Send + Sync + 'static,
+    C: Clock,
+{
+    /// Like [`Print::done`], but first lists how long each bullet took and repeats every
+    /// warning.
+    ///
+    /// Warnings emitted early in a long build scroll out of view. The summary repeats them
+    /// right before the end of the output, along with the number of warnings that were hidden
+    /// by [`Print::suppress_warnings`]. Nothing is added when there are no finished bullets and
+    /// no warnings.
+    ///
+    /// ```rust
+    /// use bullet_stream::{strip_ansi, Print};
+    ///
+    /// let output = Print::new(Vec::new())
+    ///     .without_header()
+    ///     .warning("No `Gemfile.lock` found")
+    ///     .bullet("Ruby version")
+    ///     .done()
+    ///     .bullet("Installing gems")
+    ///     .done()
+    ///     .done_with_summary();
+    ///
+    /// let output = strip_ansi(String::from_utf8_lossy(&output));
+    /// assert!(output.contains("- Summary\n  - Ruby version ...... < 0.1s\n"));
+    /// assert!(output.contains("  - Installing gems ... < 0.1s\n  - 1 warning, repeated below\n"));
+    /// ```
+    pub fn done_with_summary(self) -> W {
+        self.try_done_with_summary()
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::done_with_summary`], but returns a [`PrintError`] instead of panicking
+    /// when the output cannot be written.
+    pub fn try_done_with_summary(mut self) -> Result<W, PrintError<W>> {
+        let sections = self.sections.finished();
+        let warning_line = warning_line(self.warning_counts());
+        let io = &mut self.state.write;
+        let result = if sections.is_empty() && warning_line.is_none() {
+            Ok(())
+        } else {
+            writeln_now(io, Self::style("Summary"))
+                .and_then(|()| {
+                    section_rows(&sections)
+                        .into_iter()
+                        .chain(warning_line)
+                        .try_for_each(|row| writeln_now(io, format!("  - {row}")))
+                })
+                .and_then(|()| {
+                    self.warnings
+                        .shown_messages()
+                        .into_iter()
+                        .try_for_each(|message| write_paragraph(io, &ANSI::Yellow, message))
+                })
+        };
+
+        match result {
+            Ok(()) => self.try_done(),
+            Err(error) => Err(PrintError::new(self.state.write.into_inner(), error)),
+        }
+    }
+}
+
+/// One row per section, with dots between the name and the duration so the durations line
+/// up, for example `Ruby version ...... 1.2s`.
+fn section_rows(sections: &[(String, Duration)]) -> Vec<String> {
+    let width = |name: &str| strip_ansi(name).chars().count();
+    let widest = sections
+        .iter()
+        .map(|(name, _)| width(name))
+        .max()
+        .unwrap_or_default();
+    sections
+        .iter()
+        .map(|(name, duration)| {
+            let dots = ".".repeat(widest - width(name) + 3);
+            format!("{name} {dots} {}", duration_format::human(duration))
+        })
+        .collect()
+}
+
+fn warning_line(counts: WarningCounts) -> Option<String> {
+    let warnings = |count| {
+        if count == 1 {
+            format!("{count} warning")
+        } else {
+            format!("{count} warnings")
+        }
+    };
+    match (counts.shown(), counts.suppressed()) {
+        (0, 0) => None,
+        (shown, 0) => Some(format!("{}, repeated below", warnings(shown))),
+        (0, hidden) => Some(format!("{} hidden", warnings(hidden))),
+        (shown, hidden) => Some(format!(
+            "{}, repeated below, {hidden} hidden",
+            warnings(shown)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_section_rows() {
+        assert_eq!(
+            vec![
+                "Ruby version ...... 1.2s".to_string(),
+                "Installing gems ... 1m 5s".to_string()
+            ],
+            section_rows(&[
+                ("Ruby version".to_string(), Duration::from_millis(1_200)),
+                ("Installing gems".to_string(), Duration::from_secs(65)),
+            ])
+        );
+    }
+
+    #[test]
+    fn summary_repeats_warnings_and_lists_sections() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let output = print(&capture, &clock)
+            .suppress_warnings(["BP_RUBY_002"])
+            .h2("Example Buildpack")
+            .bullet("Ruby version");
+        clock.advance(Duration::from_millis(1_200));
+        output
+            .done()
+            .warning("No `Gemfile.lock` found")
+            .warning_with_id("BP_RUBY_002", "Using the default stack")
+            .bullet("Installing gems")
+            .sub_bullet("Running `bundle install`")
+            .done()
+            .done_with_summary();
+
+        assert_eq!(
+            formatdoc! {"
+
+                ## Example Buildpack
+
+                - Ruby version
+
+                ! No `Gemfile.lock` found
+
+                - Installing gems
+                  - Running `bundle install`
+                - Summary
+                  - Ruby version ...... 1.2s
+                  - Installing gems ... < 0.1s
+                  - 1 warning, repeated below, 1 hidden
+
+                ! No `Gemfile.lock` found
+
+                - Done (finished in 1.2s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn summary_without_sections_or_warnings_is_empty() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        print(&capture, &clock).without_header().done_with_summary();
+
+        assert_eq!("- Done (finished in < 0.1s)\n", capture.contents());
+    }
+}
@@ -0,0 +1,359 @@
+//! Tracking several parallel tasks with a periodic, append-only summary.
+use crate::ansi_escape::{self, ANSI};
+use crate::background_printer::{self, Driver};
+use crate::event::EventKind;
+use crate::print_error::{PrintError, PrintResult};
+use crate::util::prefix_first_rest_lines;
+use crate::{duration_format, state, strip_ansi, style, writeln_now, Clock, Print, SectionParent};
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the summary of finished tasks is printed.
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Marks tasks started by [`Print::start_tasks`] as finished.
+///
+/// Clones share the same tasks, so a clone can be moved to each thread that performs a task.
+#[derive(Clone)]
+pub struct TaskTracker {
+    tasks: Arc<Mutex<TaskList>>,
+    elapsed: Arc<dyn Fn() -> Duration + Send + Sync>,
+}
+
+#[derive(Debug)]
+struct TaskList {
+    names: Vec<String>,
+    /// Index into `names` and the time it took, in the order the tasks finished.
+    finished: Vec<(usize, Duration)>,
+}
+
+impl Debug for TaskTracker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskTracker")
+            .field("tasks", &self.tasks)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TaskTracker {
+    fn new<C: Clock>(names: Vec<String>, clock: C) -> Self {
+        let started = clock.now();
+        Self {
+            tasks: Arc::new(Mutex::new(TaskList {
+                names,
+                finished: Vec::new(),
+            })),
+            elapsed: Arc::new(move || clock.elapsed(started)),
+        }
+    }
+
+    /// Mark the task with the given name as finished.
+    ///
+    /// Names that were not passed to [`Print::start_tasks`] and tasks that already finished are
+    /// ignored.
+    pub fn finish(&self, name: impl AsRef<str>) {
+        let elapsed = (self.elapsed)();
+        let mut tasks = self.lock();
+        let index = tasks.names.iter().enumerate().position(|(index, task)| {
+            task == name.as_ref() && !tasks.finished.iter().any(|(i, _)| *i == index)
+        });
+        if let Some(index) = index {
+            tasks.finished.push((index, elapsed));
+        }
+    }
+
+    /// For example `2/3 done: ruby, node`.
+    fn summary(&self) -> String {
+        let tasks = self.lock();
+        let count = format!("{}/{} done", tasks.finished.len(), tasks.names.len());
+        if tasks.finished.is_empty() {
+            count
+        } else {
+            let names = tasks
+                .finished
+                .iter()
+                .map(|(index, _)| tasks.names[*index].as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{count}: {names}")
+        }
+    }
+
+    /// The time each task took in the order the tasks were started, `None` if it did not finish.
+    fn durations(&self) -> Vec<(String, Option<Duration>)> {
+        let tasks = self.lock();
+        tasks
+            .names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let duration = tasks
+                    .finished
+                    .iter()
+                    .find(|(i, _)| *i == index)
+                    .map(|(_, duration)| *duration);
+                (name.clone(), duration)
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TaskList> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl<W, P, C> Print<state::SubBullet<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    /// Track several named tasks that run in parallel, such as downloads.
+    ///
+    /// Like [`Print::start_timer`], but instead of dots a one line summary of the finished tasks
+    /// (for example `2/3 done: ruby, node`) is printed every few seconds. Mark a task as finished
+    /// via [`Print::tracker`]. The output is append-only, it does not move the cursor.
+    ///
+    /// This function will transition your buildpack output to [`state::Tasks`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn start_tasks<I, S>(self, s: impl AsRef<str>, names: I) -> Print<state::Tasks<W, P>, C>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.try_start_tasks(s, names)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::start_tasks`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    // The error carries the running tasks so the caller can keep using them, like
+    // `try_start_stream`.
+    #[allow(clippy::result_large_err)]
+    pub fn try_start_tasks<I, S>(
+        mut self,
+        s: impl AsRef<str>,
+        names: I,
+    ) -> PrintResult<state::Tasks<W, P>, C>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.emit(EventKind::TasksStart, s.as_ref(), Self::DEPTH, None);
+        let result = writeln_now(&mut self.state.write, Self::style(s));
+
+        let tracker = TaskTracker::new(
+            names.into_iter().map(Into::into).collect(),
+            self.clock.clone(),
+        );
+        let summary = {
+            let tracker = tracker.clone();
+            let indent = "  ".repeat(Self::DEPTH);
+            let secrets = self.state.write.secrets.clone();
+            let color = self.state.write.color;
+            move |_| {
+                let line = secrets.redact(format!("{indent}{}\n", tracker.summary()));
+                if color {
+                    ansi_escape::wrap_ansi_escape_each_line(&ANSI::Dim, line)
+                } else {
+                    strip_ansi(line)
+                }
+            }
+        };
+
+        let started = self.clock.now();
+        let clock = self.clock.clone();
+        let output = self.map_state(|state| state::Tasks {
+            started,
+            tracker,
+            write: background_printer::print_interval(
+                state.write,
+                clock,
+                background_printer::every(SUMMARY_INTERVAL),
+                String::new(),
+                summary,
+                String::new(),
+                Driver::Thread,
+            ),
+            parent: PhantomData,
+        });
+        PrintError::from_io(output, result)
+    }
+}
+
+impl<W, P, C> Print<state::Tasks<W, P>, C>
+where
+    W: Write + Send + Sync + 'static,
+    P: SectionParent<W>,
+    C: Clock,
+{
+    const DEPTH: usize = <state::SubBullet<W, P> as crate::private::Sealed<W>>::DEPTH;
+
+    /// A handle to mark tasks as finished. It can be cloned and sent to other threads.
+    ///
+    /// ```rust
+    /// use bullet_stream::{strip_ansi, Print};
+    ///
+    /// let tasks = Print::new(Vec::new())
+    ///     .without_header()
+    ///     .bullet("Dependencies")
+    ///     .start_tasks("Installing", ["ruby", "node"]);
+    ///
+    /// let tracker = tasks.tracker();
+    /// std::thread::spawn(move || tracker.finish("node"))
+    ///     .join()
+    ///     .unwrap();
+    /// tasks.tracker().finish("ruby");
+    ///
+    /// let output = tasks.done().done().done();
+    /// assert_eq!(
+    ///     indoc::indoc! {"
+    ///         - Dependencies
+    ///           - Installing
+    ///             0/2 done
+    ///             - ruby (< 0.1s)
+    ///             - node (< 0.1s)
+    ///           - Done (< 0.1s)
+    ///         - Done (finished in < 0.1s)
+    ///     "},
+    ///     strip_ansi(String::from_utf8_lossy(&output))
+    /// );
+    /// ```
+    #[must_use]
+    pub fn tracker(&self) -> TaskTracker {
+        self.state.tracker.clone()
+    }
+
+    /// Stop printing the summary and print how long each task took.
+    ///
+    /// Tasks that were not marked as finished are reported as `(not finished)`. Transitions
+    /// back to a [`state::SubBullet`].
+    #[must_use]
+    pub fn done(self) -> Print<state::SubBullet<W, P>, C> {
+        self.try_done().expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::done`], but returns a [`PrintError`] instead of panicking when the
+    /// output cannot be written.
+    pub fn try_done(self) -> PrintResult<state::SubBullet<W, P>, C> {
+        let duration = self.clock.elapsed(self.state.started);
+        self.emit(EventKind::TasksDone, "Done", Self::DEPTH, Some(duration));
+        let mut result = Ok(());
+        let output = self.map_state(|state| {
+            let (mut io, summary_result) = match state.write.stop() {
+                Ok(stopped) => stopped,
+                // Stdlib docs recommend using `resume_unwind` to resume the thread panic
+                // <https://doc.rust-lang.org/std/thread/type.Result.html>
+                Err(e) => std::panic::resume_unwind(e),
+            };
+
+            let indent = "  ".repeat(Self::DEPTH);
+            result = summary_result.and_then(|()| {
+                for (name, task_duration) in state.tracker.durations() {
+                    let details = match task_duration {
+                        Some(task_duration) => duration_format::human(&task_duration),
+                        None => String::from("not finished"),
+                    };
+                    writeln_now(
+                        &mut io,
+                        prefix_first_rest_lines(
+                            &format!("{indent}- "),
+                            &format!("{indent}  "),
+                            &format!("{} {}", name.trim(), style::details(details)),
+                        ),
+                    )?;
+                }
+                writeln_now(
+                    &mut io,
+                    Print::<state::SubBullet<W, P>, C>::style(format!(
+                        "Done {}",
+                        style::details(duration_format::human(&duration))
+                    )),
+                )
+            });
+            state::SubBullet {
+                write: io,
+                parent: PhantomData,
+            }
+        });
+
+        PrintError::from_io(output, result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_summary() {
+        let clock = ManualClock::new();
+        let tracker = TaskTracker::new(
+            vec![
+                String::from("ruby"),
+                String::from("node"),
+                String::from("ruby"),
+            ],
+            clock.clone(),
+        );
+        assert_eq!("0/3 done", tracker.summary());
+
+        clock.advance(Duration::from_secs(1));
+        tracker.finish("node");
+        tracker.finish("node");
+        tracker.finish("unknown");
+        clock.advance(Duration::from_secs(1));
+        tracker.clone().finish("ruby");
+        assert_eq!("2/3 done: node, ruby", tracker.summary());
+
+        assert_eq!(
+            vec![
+                (String::from("ruby"), Some(Duration::from_secs(2))),
+                (String::from("node"), Some(Duration::from_secs(1))),
+                (String::from("ruby"), None),
+            ],
+            tracker.durations()
+        );
+    }
+
+    #[test]
+    fn tasks_print_a_summary() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let tasks = print(&capture, &clock)
+            .without_header()
+            .bullet("Dependencies")
+            .start_tasks("Installing", ["ruby", "node", "yarn"]);
+        let tracker = tasks.tracker();
+
+        clock.advance(Duration::from_secs(1));
+        tracker.finish("ruby");
+        clock.advance(Duration::from_secs(5));
+        tracker.finish("node");
+        tasks.done().done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Dependencies
+                  - Installing
+                    0/3 done
+                    2/3 done: ruby, node
+                    - ruby (1.0s)
+                    - node (6.0s)
+                    - yarn (not finished)
+                  - Done (6.0s)
+                - Done (finished in 6.0s)
+            "},
+            capture.contents()
+        );
+    }
+}
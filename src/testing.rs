@@ -0,0 +1,249 @@
+//! Helpers for testing code that writes output with [`Print`].
+//!
+//! Enable with the `testing` feature, usually as a dev dependency:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! bullet_stream = { version = "*", features = ["testing"] }
+//! ```
+//!
+//! Output written by [`print()`] goes to a [`Capture`] buffer, and every duration is read from a
+//! [`ManualClock`] which only advances when told to. Durations are rendered as `< 0.1s` and
+//! timers print no extra dots unless the test advances the clock, so assertions do not depend
+//! on how fast the test runs:
+//!
+//! ```rust
+//! use bullet_stream::assert_output_eq;
+//! use bullet_stream::testing::{print, Capture, ManualClock};
+//! use indoc::indoc;
+//! use std::time::Duration;
+//!
+//! let capture = Capture::new();
+//! let clock = ManualClock::new();
+//!
+//! let timer = print(&capture, &clock)
+//!     .without_header()
+//!     .bullet("Ruby version")
+//!     .start_timer("Installing");
+//! clock.advance(Duration::from_secs(3));
+//! timer.done().done().done();
+//!
+//! assert_output_eq!(
+//!     indoc! {"
+//!         - Ruby version
+//!           - Installing ...... (3.0s)
+//!         - Done (finished in 3.0s)
+//!     "},
+//!     capture.contents()
+//! );
+//! ```
+use crate::{state, strip_ansi, ColorChoice, Print};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+pub use crate::ManualClock;
+
+/// Create a [`Print`] that writes to the capture buffer and reads time from the clock.
+///
+/// Colors are disabled so the output does not depend on the environment. Call
+/// [`Print::color`] to enable them.
+#[must_use]
+pub fn print(capture: &Capture, clock: &ManualClock) -> Print<state::Header<Capture>, ManualClock> {
+    Print::new(capture.clone())
+        .color(ColorChoice::Never)
+        .clock(clock.clone())
+}
+
+/// An in-memory writer that can be inspected while it's owned by a [`Print`].
+///
+/// Clones share the same buffer.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    io: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Capture {
+    /// An empty buffer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far.
+    #[must_use]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.io
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Everything written so far, as a string with ANSI escape codes removed.
+    #[must_use]
+    pub fn contents(&self) -> String {
+        strip_ansi(String::from_utf8_lossy(&self.bytes()))
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.io
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Asserts that two strings are equal, showing a line diff when they are not.
+///
+/// Takes the expected value first, then the actual value.
+///
+/// ```rust
+/// use bullet_stream::assert_output_eq;
+///
+/// assert_output_eq!("- Done\n", String::from("- Done\n"));
+/// ```
+#[macro_export]
+macro_rules! assert_output_eq {
+    ($expected:expr, $actual:expr $(,)?) => {
+        if let Some(diff) = $crate::testing::diff(
+            ::std::convert::AsRef::<str>::as_ref(&$expected),
+            ::std::convert::AsRef::<str>::as_ref(&$actual),
+        ) {
+            panic!("{diff}");
+        }
+    };
+}
+
+/// Returns a line diff of the two strings, or `None` when they are equal.
+///
+/// Lines only in `expected` are prefixed with `-`, lines only in `actual` are prefixed with `+`.
+#[must_use]
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+
+    let expected = expected.split('\n').collect::<Vec<_>>();
+    let actual = actual.split('\n').collect::<Vec<_>>();
+
+    // Length of the longest common subsequence of `expected[i..]` and `actual[j..]`
+    let mut lengths = vec![vec![0_usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = String::from("Output did not match (-expected +actual):\n");
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            result.push_str(&format!(" {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            result.push_str(&format!("-{}\n", expected[i]));
+            i += 1;
+        } else {
+            result.push_str(&format!("+{}\n", actual[j]));
+            j += 1;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::formatdoc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(None, diff("same\n", "same\n"));
+
+        let expected = formatdoc! {"
+            - Ruby version
+              - Installing
+            - Done
+        "};
+        let actual = formatdoc! {"
+            - Ruby version
+              - Downloading
+              - Installing
+            - Done!
+        "};
+        assert_eq!(
+            [
+                "Output did not match (-expected +actual):",
+                " - Ruby version",
+                "+  - Downloading",
+                "   - Installing",
+                "-- Done",
+                "+- Done!",
+                " ",
+                "",
+            ]
+            .join("\n"),
+            diff(&expected, &actual).unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "-- Done")]
+    fn assert_output_eq_panics() {
+        assert_output_eq!("- Done\n", "- Done!\n");
+    }
+
+    #[test]
+    fn durations_are_deterministic() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let output = print(&capture, &clock)
+            .h2("Example Buildpack")
+            .bullet("Streaming");
+        let mut stream = output.start_stream("Compiling");
+        std::thread::sleep(Duration::from_millis(150));
+        writeln!(stream, "hello").unwrap();
+        let output = stream.done().done();
+
+        let mut output = output.bullet("Waiting");
+        output.stream_with("Testing", |_, _| {
+            clock.advance(Duration::from_millis(1_500));
+        });
+        clock.advance(Duration::from_secs(90));
+        output.done().done();
+
+        assert_output_eq!(
+            formatdoc! {"
+
+                ## Example Buildpack
+
+                - Streaming
+                  - Compiling
+
+                      hello
+
+                  - Done (< 0.1s)
+                - Waiting
+                  - Testing
+
+                  - Done (1.5s)
+                - Done (finished in 1m 31s)
+            "},
+            capture.contents()
+        );
+    }
+}
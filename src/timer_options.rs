@@ -0,0 +1,222 @@
+use crate::duration_format;
+use std::time::Duration;
+
+/// Configures the dots printed by a timer.
+///
+/// By default a timer prints a dot every second. For long-running tasks, such as a compile
+/// that takes ten minutes, that's a line of 600 dots. Pass options to
+/// [`crate::Print::start_timer_with_options`] to print fewer dots or to mark the elapsed time:
+///
+/// ```rust
+/// use bullet_stream::{Print, TimerOptions};
+/// use std::time::Duration;
+///
+/// let timer = Print::new(std::io::stdout())
+///     .h2("Example Buildpack")
+///     .bullet("Compiling")
+///     .start_timer_with_options(
+///         "Running `make`",
+///         TimerOptions::new()
+///             .backoff(Duration::from_secs(30))
+///             .elapsed_markers(Duration::from_secs(60)),
+///     );
+///
+/// timer.done().done().done();
+/// ```
+///
+/// With these options the output of a compile that takes two and a half minutes looks like:
+///
+/// ```text
+/// - Compiling
+///   - Running `make` ............................. (1m) ......... (2m) ..... (2m 30s)
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimerOptions {
+    pub(crate) interval: Duration,
+    pub(crate) tick: String,
+    pub(crate) backoff: Option<Duration>,
+    pub(crate) elapsed_markers: Option<Duration>,
+}
+
+impl Default for TimerOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            tick: String::from("."),
+            backoff: None,
+            elapsed_markers: None,
+        }
+    }
+}
+
+impl TimerOptions {
+    /// Options that print a dot every second.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The time between ticks.
+    ///
+    /// Intervals shorter than a millisecond are treated as a millisecond. Defaults to one second.
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The text printed for each tick.
+    ///
+    /// Defaults to `.`.
+    #[must_use]
+    pub fn tick(mut self, tick: impl Into<String>) -> Self {
+        self.tick = tick.into();
+        self
+    }
+
+    /// Print ticks less often over time.
+    ///
+    /// The time between ticks doubles after every ten ticks, until it reaches `max_interval`.
+    ///
+    /// Defaults to no backoff.
+    #[must_use]
+    pub fn backoff(mut self, max_interval: Duration) -> Self {
+        self.backoff = Some(max_interval);
+        self
+    }
+
+    /// Print the elapsed time, such as `(1m)`, between the ticks every time the given duration
+    /// passes.
+    ///
+    /// The marker is printed with the first tick after the duration passes. Durations shorter
+    /// than a second are treated as a second. Defaults to no markers.
+    #[must_use]
+    pub fn elapsed_markers(mut self, every: Duration) -> Self {
+        self.elapsed_markers = Some(every);
+        self
+    }
+
+    /// The time since the start of the timer at which the given tick is due.
+    pub(crate) fn schedule(&self) -> impl Fn(u32) -> Duration + Send + 'static {
+        let interval = self.interval.max(Duration::from_millis(1));
+        let backoff = self.backoff;
+        move |tick| match backoff {
+            None => interval.saturating_mul(tick),
+            Some(max_interval) => {
+                let mut due = Duration::ZERO;
+                let mut gap = interval;
+                let mut remaining = tick;
+                while remaining > 0 {
+                    let ticks = remaining.min(10);
+                    due = due.saturating_add(gap.saturating_mul(ticks));
+                    remaining -= ticks;
+                    gap = gap.saturating_mul(2).min(max_interval).max(interval);
+                }
+                due
+            }
+        }
+    }
+
+    /// The text printed when the timer starts, for each tick and when the timer stops.
+    ///
+    /// Each tick and marker is passed through the `style` function.
+    pub(crate) fn ticks(
+        &self,
+        style: impl Fn(&str) -> String + Send + 'static,
+    ) -> (
+        String,
+        impl FnMut(Duration) -> String + Send + 'static,
+        String,
+    ) {
+        let start = style(&format!(" {}", self.tick));
+        let end = style(&format!("{} ", self.tick));
+
+        let tick = self.tick.clone();
+        let every = self
+            .elapsed_markers
+            .map(|every| every.max(Duration::from_secs(1)));
+        let mut markers = 0;
+        let tick = move |due: Duration| {
+            let marker = every.and_then(|every| {
+                let passed = u32::try_from(due.as_nanos() / every.as_nanos()).unwrap_or(u32::MAX);
+                (passed > markers).then(|| {
+                    markers = passed;
+                    let elapsed = duration_format::compact(&every.saturating_mul(passed));
+                    style(&format!(" ({elapsed}) "))
+                })
+            });
+            marker.unwrap_or_default() + &style(&tick)
+        };
+
+        (start, tick, end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_schedule() {
+        let schedule = TimerOptions::new().schedule();
+        assert_eq!(Duration::ZERO, schedule(0));
+        assert_eq!(Duration::from_secs(3), schedule(3));
+
+        let schedule = TimerOptions::new()
+            .interval(Duration::from_secs(2))
+            .backoff(Duration::from_secs(5))
+            .schedule();
+        assert_eq!(Duration::from_secs(20), schedule(10));
+        assert_eq!(Duration::from_secs(24), schedule(11));
+        assert_eq!(Duration::from_secs(60), schedule(20));
+        assert_eq!(Duration::from_secs(65), schedule(21));
+
+        let schedule = TimerOptions::new().interval(Duration::ZERO).schedule();
+        assert_eq!(Duration::from_millis(5), schedule(5));
+    }
+
+    #[test]
+    fn test_ticks() {
+        let (start, mut tick, end) = TimerOptions::new()
+            .tick("*")
+            .elapsed_markers(Duration::from_secs(60))
+            .ticks(|tick: &str| tick.to_string());
+        assert_eq!(" *", start);
+        assert_eq!("* ", end);
+
+        let ticks = [0, 30, 59, 60, 61, 150]
+            .into_iter()
+            .map(|seconds| tick(Duration::from_secs(seconds)))
+            .collect::<String>();
+        assert_eq!("*** (1m) ** (2m) *", ticks);
+    }
+
+    #[test]
+    fn timer_with_options() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let timer = print(&capture, &clock)
+            .without_header()
+            .bullet("Compiling")
+            .start_timer_with_options(
+                "Running `make`",
+                TimerOptions::new()
+                    .backoff(Duration::from_secs(30))
+                    .elapsed_markers(Duration::from_secs(60)),
+            );
+        clock.advance(Duration::from_secs(150));
+        timer.done().done().done();
+
+        assert_eq!(
+            formatdoc! {"
+                - Compiling
+                  - Running `make` ............................. (1m) ......... (2m) ..... (2m 30s)
+                - Done (finished in 2m 30s)
+            "},
+            capture.contents()
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::panic_guard::PanicGuard;
+use crate::redact::Secrets;
 use std::any::{Any, TypeId};
 use std::fmt::Debug;
 use std::io::Write;
@@ -62,19 +64,47 @@ pub(crate) fn prefix_lines<F: Fn(usize, &str) -> String>(contents: &str, f: F) -
 /// exposing that information to the caller.
 #[derive(Debug)]
 pub(crate) struct ParagraphInspectWrite<W> {
-    pub(crate) inner: W,
+    // Wrapped in an `Option` so it can be taken out by `into_inner`, even though this struct
+    // implements `Drop` to write a panic message.
+    //
+    // See: https://rustwiki.org/en/error-index/#E0509
+    pub(crate) inner: Option<W>,
     pub(crate) was_paragraph: bool,
     pub(crate) newlines_since_last_char: usize,
+    /// When false, ANSI escape codes are removed from messages written via `writeln_now`.
+    pub(crate) color: bool,
+    /// Values redacted from messages written via `writeln_now` and from streamed output.
+    pub(crate) secrets: Secrets,
+    /// When set, a panic message is written when this is dropped while panicking.
+    pub(crate) panic_guard: Option<PanicGuard<W>>,
 }
 
-impl<W> ParagraphInspectWrite<W> {
+impl<W: Write> ParagraphInspectWrite<W> {
     pub(crate) fn new(io: W) -> Self {
         Self {
-            inner: io,
+            inner: Some(io),
             newlines_since_last_char: 0,
             was_paragraph: false,
+            color: true,
+            secrets: Secrets::default(),
+            panic_guard: None,
         }
     }
+
+    /// Returns the wrapped writer.
+    pub(crate) fn into_inner(mut self) -> W {
+        match self.inner.take() {
+            Some(inner) => inner,
+            // Only `into_inner` takes the value, and it consumes `self`
+            None => unreachable!("self.inner will never be None"),
+        }
+    }
+
+    fn inner_mut(&mut self) -> std::io::Result<&mut W> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
 }
 
 impl<W: Write> Write for ParagraphInspectWrite<W> {
@@ -90,11 +120,11 @@ impl<W: Write> Write for ParagraphInspectWrite<W> {
         }
 
         self.was_paragraph = self.newlines_since_last_char > 1;
-        self.inner.write(buf)
+        self.inner_mut()?.write(buf)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
+        self.inner_mut()?.flush()
     }
 }
 
@@ -154,20 +184,60 @@ where
     }
 }
 
+/// A writer that always fails, simulating a closed pipe.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct ClosedWriter;
+
+#[cfg(test)]
+impl Write for ClosedWriter {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Which of the two writers of a `stream_with` closure a message was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Source {
+    Stdout,
+    Stderr,
+}
+
 /// Allows a `std::sync::mpsc::Sender` to be used as a `std::io::Write`.
+///
+/// Each message is tagged with the [`Source`] of the writer.
 pub(crate) struct MpscWriter {
-    sender: std::sync::mpsc::Sender<Vec<u8>>,
+    sender: std::sync::mpsc::Sender<(Source, Vec<u8>)>,
+    source: Source,
 }
 
 impl MpscWriter {
-    pub(crate) fn new(sender: std::sync::mpsc::Sender<Vec<u8>>) -> Self {
-        Self { sender }
+    /// A writer for [`Source::Stdout`].
+    pub(crate) fn new(sender: std::sync::mpsc::Sender<(Source, Vec<u8>)>) -> Self {
+        Self {
+            sender,
+            source: Source::Stdout,
+        }
+    }
+
+    /// A writer to the same channel that tags messages with the given source.
+    pub(crate) fn with_source(&self, source: Source) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            source,
+        }
     }
 }
 
 impl Write for MpscWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.sender.send(buf.to_vec()).expect("Channel to be open");
+        self.sender
+            .send((self.source, buf.to_vec()))
+            .expect("Channel to be open");
         Ok(buf.len())
     }
 
@@ -178,9 +248,7 @@ impl Write for MpscWriter {
 
 impl Clone for MpscWriter {
     fn clone(&self) -> Self {
-        Self {
-            sender: self.sender.clone(),
-        }
+        self.with_source(self.source)
     }
 }
 
@@ -200,11 +268,11 @@ impl Clone for MpscWriter {
 pub(crate) fn mpsc_stream_to_output<S, L, F>(mut stream: S, mut output: L) -> F
 where
     S: FnMut(MpscWriter) -> F,
-    L: FnMut(mpsc::Receiver<Vec<u8>>) + Send,
+    L: FnMut(mpsc::Receiver<(Source, Vec<u8>)>) + Send,
     F: Any,
 {
     thread::scope(|scope| {
-        let (send, recv) = mpsc::channel::<Vec<u8>>();
+        let (send, recv) = mpsc::channel::<(Source, Vec<u8>)>();
         // The receiver is moved into the background thread where it waits on input from the senders.
         scope.spawn(move || {
             output(recv);
@@ -310,7 +378,7 @@ mod test {
                 writeln!(send, "World").unwrap();
             },
             |recv| {
-                for message in recv {
+                for (_, message) in recv {
                     output.extend(message.iter());
                 }
             },
@@ -318,6 +386,26 @@ mod test {
         assert_eq!("Hello\nWorld\n", &String::from_utf8_lossy(&output));
     }
 
+    #[test]
+    fn test_mpsc_streaming_tags_source() {
+        let mut sources = Vec::new();
+        mpsc_stream_to_output(
+            |mut stdout| {
+                let mut stderr = stdout.with_source(Source::Stderr);
+                writeln!(stdout, "Hello").unwrap();
+                writeln!(stderr, "Warning").unwrap();
+                writeln!(stdout.clone(), "World").unwrap();
+            },
+            |recv| {
+                sources.extend(recv.into_iter().map(|(source, _)| source));
+            },
+        );
+        assert_eq!(
+            vec![Source::Stdout, Source::Stderr, Source::Stdout],
+            sources
+        );
+    }
+
     #[test]
     fn test_mpsc_streaming_cannot_deadlock() {
         let result = std::panic::catch_unwind(|| {
@@ -331,7 +419,7 @@ mod test {
                     send
                 },
                 |recv| {
-                    for message in recv {
+                    for (_, message) in recv {
                         output.extend(message.iter());
                     }
                 },
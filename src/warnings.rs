@@ -0,0 +1,348 @@
+//! Stable identifiers for warnings and errors, and hiding warnings by identifier. See
+//! [`crate::Print::warning_with_id`].
+use crate::event::EventKind;
+use crate::print_error::{PrintError, PrintResult};
+use crate::{state, AnnounceSupportedState, Clock, Print};
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// How many warnings were written and how many were hidden, see [`Print::warning_counts`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WarningCounts {
+    shown: usize,
+    suppressed: usize,
+}
+
+impl WarningCounts {
+    /// Warnings that were written to the output, with or without an identifier.
+    #[must_use]
+    pub fn shown(&self) -> usize {
+        self.shown
+    }
+
+    /// Warnings that were not written because their identifier was suppressed.
+    #[must_use]
+    pub fn suppressed(&self) -> usize {
+        self.suppressed
+    }
+}
+
+/// The suppressed identifiers and the warning counts.
+///
+/// The state is shared, so the printers of [`Print::parallel`] tasks add to the counts of the
+/// printer that created them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Warnings(Arc<Mutex<WarningLog>>);
+
+#[derive(Debug, Default)]
+struct WarningLog {
+    suppressed_ids: Vec<String>,
+    /// The environment variable the identifiers were read from, mentioned in each warning
+    env_var: Option<String>,
+    counts: WarningCounts,
+    messages: Vec<String>,
+}
+
+impl Warnings {
+    fn suppress(&self, ids: impl IntoIterator<Item = String>) {
+        let mut log = self.lock();
+        for id in ids {
+            if !log.suppressed_ids.contains(&id) {
+                log.suppressed_ids.push(id);
+            }
+        }
+    }
+
+    fn is_suppressed(&self, id: &str) -> bool {
+        self.lock()
+            .suppressed_ids
+            .iter()
+            .any(|suppressed| suppressed == id)
+    }
+
+    fn record_suppressed(&self) {
+        self.lock().counts.suppressed += 1;
+    }
+
+    /// Counts a warning that was written and keeps its message for the summary.
+    pub(crate) fn record_shown(&self, message: &str) {
+        let mut log = self.lock();
+        log.counts.shown += 1;
+        log.messages.push(message.to_string());
+    }
+
+    /// The messages of the warnings that were written, in order.
+    pub(crate) fn shown_messages(&self) -> Vec<String> {
+        self.lock().messages.clone()
+    }
+
+    fn counts(&self) -> WarningCounts {
+        self.lock().counts
+    }
+
+    fn env_var(&self) -> Option<String> {
+        self.lock().env_var.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WarningLog> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Splits a comma separated list, such as `BP_RUBY_001, BP_RUBY_002`, into identifiers.
+fn parse_ids(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl<T, C> Print<T, C> {
+    /// How many warnings were written, and how many were hidden by
+    /// [`Print::suppress_warnings`] or [`Print::suppress_warnings_from_env`], so far.
+    #[must_use]
+    pub fn warning_counts(&self) -> WarningCounts {
+        self.warnings.counts()
+    }
+}
+
+impl<W, C> Print<state::Header<W>, C>
+where
+    W: Write,
+    C: Clock,
+{
+    /// Hide warnings with the given identifiers, see [`Print::warning_with_id`].
+    ///
+    /// Hidden warnings are not written, but they are still counted by
+    /// [`Print::warning_counts`] and sent to the [`Print::event_stream`] as
+    /// `warning_suppressed` events. Errors cannot be hidden.
+    #[must_use]
+    pub fn suppress_warnings<I, S>(self, ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.warnings.suppress(ids.into_iter().map(Into::into));
+        self
+    }
+
+    /// Hide warnings with the identifiers listed in an environment variable, separated by
+    /// commas, see [`Print::suppress_warnings`].
+    ///
+    /// Each warning with an identifier tells the user which variable to set to hide it. A
+    /// missing variable hides nothing.
+    ///
+    /// ```rust
+    /// use bullet_stream::{strip_ansi, Print};
+    ///
+    /// std::env::set_var("BP_SUPPRESS_WARNINGS", "BP_RUBY_001,BP_RUBY_002");
+    ///
+    /// let output = Print::new(Vec::new())
+    ///     .suppress_warnings_from_env("BP_SUPPRESS_WARNINGS")
+    ///     .h2("Example Buildpack")
+    ///     .warning_with_id("BP_RUBY_001", "Ruby 2.7 is end of life")
+    ///     .done();
+    ///
+    /// assert!(!strip_ansi(String::from_utf8_lossy(&output)).contains("Ruby 2.7 is end of life"));
+    /// ```
+    #[must_use]
+    pub fn suppress_warnings_from_env(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let ids = std::env::var(&name)
+            .map(|value| parse_ids(&value))
+            .unwrap_or_default();
+        self.warnings.lock().env_var = Some(name);
+        self.suppress_warnings(ids)
+    }
+}
+
+/// Used for announcements such as warning and error states
+#[allow(private_bounds)]
+impl<S, C> Print<S, C>
+where
+    S: AnnounceSupportedState,
+    C: Clock,
+{
+    /// Emit a warning, like [`Print::warning`], with a stable identifier such as
+    /// `BP_RUBY_001`.
+    ///
+    /// The identifier is written below the message so users can search for it and report it.
+    /// Unlike the message, it should never change. Warnings with an identifier can be hidden
+    /// with [`Print::suppress_warnings`] or [`Print::suppress_warnings_from_env`].
+    ///
+    /// ```rust
+    /// use bullet_stream::{strip_ansi, Print};
+    ///
+    /// let output = Print::new(Vec::new())
+    ///     .without_header()
+    ///     .warning_with_id("BP_RUBY_001", "Ruby 2.7 is end of life")
+    ///     .done();
+    ///
+    /// assert!(strip_ansi(String::from_utf8_lossy(&output))
+    ///     .contains("! Ruby 2.7 is end of life\n!\n! Warning ID: BP_RUBY_001\n"));
+    /// ```
+    #[must_use]
+    pub fn warning_with_id(self, id: impl AsRef<str>, s: impl AsRef<str>) -> Print<S, C> {
+        self.try_warning_with_id(id, s)
+            .expect("Output error: UI writer closed")
+    }
+
+    /// Like [`Print::warning_with_id`], but returns a [`PrintError`] instead of panicking when
+    /// the output cannot be written.
+    pub fn try_warning_with_id(self, id: impl AsRef<str>, s: impl AsRef<str>) -> PrintResult<S, C> {
+        let id = id.as_ref().trim();
+        let mut message = format!("{}\n\nWarning ID: {id}", s.as_ref().trim());
+        if let Some(name) = self.warnings.env_var() {
+            message.push_str(&format!(
+                "\nTo hide this warning, add `{id}` to the `{name}` environment variable."
+            ));
+        }
+
+        if self.warnings.is_suppressed(id) {
+            self.warnings.record_suppressed();
+            self.emit(EventKind::WarningSuppressed, message, 0, None);
+            Ok(self)
+        } else {
+            self.try_warning(message)
+        }
+    }
+
+    /// Emit an error and end the build output, like [`Print::error`], with a stable identifier
+    /// such as `BP_RUBY_002` written below the message.
+    pub fn error_with_id(self, id: impl AsRef<str>, s: impl AsRef<str>) {
+        self.try_error_with_id(id, s)
+            .expect("Output error: UI writer closed");
+    }
+
+    /// Like [`Print::error_with_id`], but returns a [`PrintError`] instead of panicking when
+    /// the output cannot be written.
+    pub fn try_error_with_id(
+        self,
+        id: impl AsRef<str>,
+        s: impl AsRef<str>,
+    ) -> Result<(), PrintError<()>> {
+        self.try_error(format!(
+            "{}\n\nError ID: {}",
+            s.as_ref().trim(),
+            id.as_ref().trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{print, Capture, ManualClock};
+    use indoc::formatdoc;
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(
+            vec!["BP_RUBY_001", "BP_RUBY_002"],
+            parse_ids(" BP_RUBY_001,,BP_RUBY_002 ,")
+        );
+        assert!(parse_ids("").is_empty());
+    }
+
+    #[test]
+    fn suppressed_warnings_are_counted() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        let output = print(&capture, &clock)
+            .suppress_warnings(["BP_RUBY_002"])
+            .without_header()
+            .warning_with_id("BP_RUBY_001", "Ruby 2.7 is end of life")
+            .warning_with_id("BP_RUBY_002", "No `Gemfile.lock` found")
+            .warning("Using the default stack");
+        assert_eq!(2, output.warning_counts().shown());
+        assert_eq!(1, output.warning_counts().suppressed());
+        output.done();
+
+        assert_eq!(
+            formatdoc! {"
+
+                ! Ruby 2.7 is end of life
+                !
+                ! Warning ID: BP_RUBY_001
+
+                ! Using the default stack
+
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+    }
+
+    #[test]
+    fn env_var_is_mentioned_and_suppressed_warnings_are_events() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+        let events = crate::util::LockedWriter::new(Vec::new());
+
+        print(&capture, &clock)
+            .event_stream(events.clone())
+            .suppress_warnings_from_env("BULLET_STREAM_TEST_UNSET_VARIABLE")
+            .suppress_warnings(["BP_RUBY_002"])
+            .without_header()
+            .warning_with_id("BP_RUBY_001", "Ruby 2.7 is end of life")
+            .warning_with_id("BP_RUBY_002", "No `Gemfile.lock` found")
+            .done();
+
+        assert_eq!(
+            formatdoc! {"
+
+                ! Ruby 2.7 is end of life
+                !
+                ! Warning ID: BP_RUBY_001
+                ! To hide this warning, add `BP_RUBY_001` to the `BULLET_STREAM_TEST_UNSET_VARIABLE` environment variable.
+
+                - Done (finished in < 0.1s)
+            "},
+            capture.contents()
+        );
+        let events = String::from_utf8(events.unwrap()).unwrap();
+        assert!(
+            events.contains(r#"{"kind":"warning_suppressed","text":"No `Gemfile.lock` found\n\nWarning ID: BP_RUBY_002"#),
+            "{events}"
+        );
+    }
+
+    #[test]
+    fn failed_warnings_are_not_counted() {
+        let output = Print::new(crate::util::ClosedWriter)
+            .without_header()
+            .try_warning("No `Gemfile.lock` found")
+            .unwrap_err()
+            .into_inner()
+            .try_warning_with_id("BP_RUBY_001", "Ruby 2.7 is end of life")
+            .unwrap_err()
+            .into_inner();
+
+        assert_eq!(WarningCounts::default(), output.warning_counts());
+        assert!(output.warnings.shown_messages().is_empty());
+    }
+
+    #[test]
+    fn error_with_id() {
+        let capture = Capture::new();
+        let clock = ManualClock::new();
+
+        print(&capture, &clock)
+            .without_header()
+            .error_with_id("BP_RUBY_002", "No `Gemfile.lock` found");
+
+        assert_eq!(
+            formatdoc! {"
+
+                ! No `Gemfile.lock` found
+                !
+                ! Error ID: BP_RUBY_002
+
+            "},
+            capture.contents()
+        );
+    }
+}